@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+use chip8_lib::args::{next_value, parse_number};
+use chip8_lib::chip_8::Mode;
+use chip8_lib::cpu_state::Quirk;
+
+pub const USAGE: &str = "Usage: chip8_trace <ROM> [OPTIONS]
+
+Runs a ROM headlessly and writes the state before every instruction, or compares it
+with a trace in the same format written by another emulator.
+
+Options:
+  --mode <MODE>           chip8, schip, xochip or experimental (default: chip8)
+  --ipf <N>               instructions per frame (default: depends on mode)
+  --frames <N>            number of 60 Hz frames to run (default: 600)
+  --quirk <NAME>=<on|off> override a quirk, can be repeated
+                          (vf-reset, shift, jump, memory, i-overflow, wrap)
+  --seed <N>              seed for the CXNN random numbers, so runs can be repeated
+                          (default: a random seed)
+  --out <FILE>            write the trace to a file instead of the standard output
+  --compare <FILE>        compare with a reference trace and report the first divergence
+  --context <N>           instructions shown before a divergence (default: 5)
+  -h, --help              print this message
+
+Trace format, one line per instruction, all values in hex:
+  PC:0200 OP:00E0 I:0000 V:000102030405060708090A0B0C0D0E0F SP:0 MEM:811C9DC5
+V holds V0 to VF, SP is the stack depth and MEM the 32 bit FNV-1a hash of all 64 KiB of memory.
+Empty lines and lines starting with # are ignored.";
+
+#[derive(Debug)]
+pub struct Options{
+    pub rom: Option<PathBuf>,
+    pub mode: Mode,
+    pub ipf: Option<u32>,
+    pub frames: u32,
+    pub quirks: Vec<(Quirk, bool)>,
+    pub seed: Option<u64>,
+    pub out: Option<PathBuf>,
+    pub compare: Option<PathBuf>,
+    pub context: usize,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options{
+            rom: None,
+            mode: Mode::Chip8,
+            ipf: None,
+            frames: 600,
+            quirks: vec![],
+            seed: None,
+            out: None,
+            compare: None,
+            context: 5,
+            help: false,
+        }
+    }
+}
+
+impl Options{
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String>{
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--mode" => options.mode = next_value(&mut args, &arg)?.parse()?,
+                "--ipf" => options.ipf = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--frames" => options.frames = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                "--quirk" => options.quirks.push(Quirk::parse_setting(&next_value(&mut args, &arg)?)?),
+                "--seed" => options.seed = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--out" => options.out = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--compare" => options.compare = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--context" => options.context = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+                _ => {
+                    if options.rom.is_some() {
+                        return Err(format!("Only one ROM can be given\n\n{}", USAGE));
+                    }
+                    options.rom = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        if options.rom.is_none() && !options.help {
+            return Err(format!("No ROM given\n\n{}", USAGE));
+        }
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,112 @@
+mod cli;
+
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+use chip8_lib::chip_8::Chip8;
+use chip8_lib::cpu_state::CpuState;
+use chip8_lib::trace::{find_divergence, parse_trace, TraceEntry, Tracer};
+use crate::cli::{Options, USAGE};
+
+fn main() -> Result<ExitCode, String> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let trace = run(&options)?;
+
+    if let Some(path) = &options.out {
+        write_trace(&trace, &mut File::create(path).map_err(|e| format!("Could not create {}: {}", path.display(), e))?)?;
+    } else if options.compare.is_none() {
+        write_trace(&trace, &mut std::io::stdout().lock())?;
+    }
+
+    if let Some(path) = &options.compare {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let reference = parse_trace(&text)?;
+
+        return Ok(match find_divergence(&reference, &trace) {
+            Some(step) => {
+                report_divergence(&reference, &trace, step, options.context);
+                ExitCode::FAILURE
+            },
+            None => {
+                println!("Traces match for all {} instructions", trace.len());
+                ExitCode::SUCCESS
+            },
+        });
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run(options: &Options) -> Result<Vec<TraceEntry>, String>{
+    let rom_file = options.rom.as_ref().expect("ROM is checked when parsing options");
+    let rom = fs::read(rom_file).map_err(|e| format!("Could not read {}: {}", rom_file.display(), e))?;
+
+    let mut chip8 = Chip8::new(options.mode);
+    if let Some(ipf) = options.ipf {
+        chip8.ipf.store(ipf, Ordering::Relaxed);
+    }
+    for &(quirk, enabled) in &options.quirks {
+        chip8.set_quirk(quirk, enabled);
+    }
+    if let Some(seed) = options.seed {
+        chip8.state.lock().unwrap().seed_rng(seed);
+    }
+    chip8.load_font_into_memory();
+    chip8.load_rom(&rom)?;
+
+    let mut tracer = Tracer::new();
+    let mut trace = vec![];
+    chip8.run_frames_traced(options.frames, |cpu| trace.push(tracer.record(cpu)));
+    Ok(trace)
+}
+
+fn write_trace(trace: &[TraceEntry], writer: &mut impl Write) -> Result<(), String>{
+    let mut writer = BufWriter::new(writer);
+    for entry in trace {
+        writeln!(writer, "{}", entry).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn report_divergence(reference: &[TraceEntry], trace: &[TraceEntry], step: usize, context: usize){
+    println!("First divergence at instruction {}:", step);
+    let start = step.saturating_sub(context);
+    for (idx, entry) in trace[start..step].iter().enumerate() {
+        println!("  {:>8}  {}  {}", start + idx, entry, describe(entry.opcode));
+    }
+
+    match (reference.get(step), trace.get(step)) {
+        (Some(expected), Some(actual)) => {
+            println!("  expected  {}", expected);
+            println!("  actual    {}", actual);
+            println!("Differs in {}", expected.diff(actual).join(", "));
+            if step > 0 {
+                println!("The state differs after {}", describe(trace[step - 1].opcode));
+            }
+        },
+        (Some(expected), None) => {
+            println!("  expected  {}", expected);
+            println!("chip8_lib stopped after {} instructions, the reference goes on", trace.len());
+        },
+        (None, Some(actual)) => {
+            println!("  actual    {}", actual);
+            println!("The reference ends after {} instructions", reference.len());
+        },
+        (None, None) => {},
+    }
+
+}
+
+fn describe(opcode: u16) -> String{
+    match CpuState::decode(opcode) {
+        Some(instruction) => instruction.to_string(),
+        None => format!("{:04X}: unknown instruction", opcode),
+    }
+}
@@ -1,11 +1,20 @@
 use std::sync::{Arc, Mutex};
 use dioxus::prelude::*;
-use chip8_lib::display::Display;
+use chip8_lib::chip_8::{HaltReason, Lifecycle};
+use chip8_lib::frame_composer::FrameComposer;
+use chip8_lib::palette::Palette;
 use chip8_lib::parameters::*;
 use crate::helpers::game::{Colors, Game};
 
 #[component]
-pub fn EmuDisplay(display: Display, game: Option<Game>, paused: bool) -> Element{
+pub fn EmuDisplay(frames: FrameComposer, game: Option<Game>, lifecycle: Lifecycle) -> Element{
+    let palette = game.as_ref().map(|game| game.colors.palette());
+    let halted_message = match lifecycle {
+        Lifecycle::Halted(HaltReason::Exit) => Some("The program exited (00FD)"),
+        Lifecycle::Halted(HaltReason::ZeroOpcode) => Some("The program ran into a 0000 opcode"),
+        _ => None,
+    };
+
     rsx! {
         div{
             class: "relative w-full",
@@ -22,7 +31,7 @@ pub fn EmuDisplay(display: Display, game: Option<Game>, paused: bool) -> Element
                                 y: "{y}",
                                 width: "1",
                                 height: "1",
-                                fill: get_pixel_color(&display, (y * DISPLAY_WIDTH) + x, &game)
+                                fill: get_pixel_color(&frames, (y * DISPLAY_WIDTH) + x, &palette)
                             }
                         }
                     }
@@ -34,7 +43,17 @@ pub fn EmuDisplay(display: Display, game: Option<Game>, paused: bool) -> Element
                     "Select game and start emulator"
                 }
             }
-            else if paused{
+            else if let Some(message) = halted_message{
+                div{
+                    class: "absolute top-0 left-0 w-full h-full bg-base-200 opacity-90 rounded-sm text-6xl md:text-8xl text-primary flex flex-col items-center justify-center text-center",
+                    p { "Game ended" }
+                    p {
+                        class: "text-2xl md:text-3xl text-secondary",
+                        "{message}, press Start to play again"
+                    }
+                }
+            }
+            else if lifecycle == Lifecycle::Paused{
                 div{
                     class: "absolute top-0 left-0 w-full h-full bg-base-200 opacity-90 rounded-sm text-6xl md:text-8xl text-primary flex flex-col items-center justify-center text-center",
                     p { "Game paused" }
@@ -48,20 +67,10 @@ pub fn EmuDisplay(display: Display, game: Option<Game>, paused: bool) -> Element
     }
 }
 
-fn get_pixel_color(display: &Display, idx: usize, game: &Option<Game>) -> String{
-    if let Some(game) = game {
-        if display.plane_1[idx] && display.plane_2[idx]{
-            game.colors.mixed.to_string()
-        }
-        else if display.plane_2[idx]{
-            game.colors.plane2.to_string()
-        }
-        else if display.plane_1[idx]{
-            game.colors.plane1.to_string()
-        }
-        else{
-            game.colors.none.to_string()
-        }
+fn get_pixel_color(frames: &FrameComposer, idx: usize, palette: &Option<Palette>) -> String{
+    if let Some(palette) = palette {
+        let (r, g, b) = frames.get_pixel_color(palette, idx);
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
     }else{
         "#000000".to_string()
     }
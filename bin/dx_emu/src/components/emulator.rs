@@ -1,54 +1,119 @@
 use dioxus::core::Element;
 use dioxus::core_macro::rsx;
 use dioxus::hooks::{use_signal};
-use chip8_lib::display::Display;
+use chip8_lib::chip_8::Lifecycle;
+use chip8_lib::frame_composer::FrameComposer;
 use dioxus::prelude::*;
+use gloo_timers::future::TimeoutFuture;
 use crate::components::{EmuDisplay, Footer, Instructions, MobileKeyboard};
 use crate::helpers::chip8_wrapper::Chip8Web;
+use crate::helpers::download::download_file;
+use chip8_lib::keymap::{KeyMap, Layout};
 use crate::helpers::game::Game;
-use crate::{KEYBOARD_EVENTS, SHOW_KEYBOARD};
+use crate::helpers::gamepad::poll_gamepads;
+use crate::helpers::storage::save_key_map;
+use crate::{KEYBOARD_EVENTS, KEY_MAP, SHOW_KEYBOARD};
+
+const SCREENSHOT_SCALE: u32 = 8;
+const FAST_FORWARD_SPEED: f32 = 4.0;
 
 #[component]
 pub fn Emulator() -> Element{
-    let mut display_signal = use_signal(|| Display::new());
-    let mut paused_signal = use_signal(|| false);
+    let mut display_signal = use_signal(FrameComposer::default);
+    let mut lifecycle_signal = use_signal(|| Lifecycle::Idle);
     let mut selected_game_signal = use_signal(|| Game::t8nks());
     let mut active_game_signal: Signal<Option<Game>> = use_signal(|| None);
     let mut chip8_signal: Signal<Option<Chip8Web>> = use_signal(|| None);
     let mut show_keyboard_signal = SHOW_KEYBOARD.signal().clone();
+    let mut slow_motion_signal = use_signal(|| 1.0f32);
+    let mut fast_forward_signal = use_signal(|| false);
+    let mut error_signal: Signal<Option<String>> = use_signal(|| None);
 
+    // Fast-forward wins over slow motion while it is on
+    let current_speed = move || if *fast_forward_signal.peek() { FAST_FORWARD_SPEED } else { *slow_motion_signal.peek() };
 
     let mut start_emu = move |game: &Game|{
         if let Some(chip8) = chip8_signal.write().as_mut(){
             chip8.stop();
-            display_signal.set(Display::new());
         }
+        display_signal.set(FrameComposer::new(game.flicker));
 
         let mut new_chip8 = Chip8Web::new(game.mode);
-        new_chip8.start(&game, &mut display_signal);
-        chip8_signal.set(Some(new_chip8));
-        active_game_signal.set(Some(game.clone()));
-
-        paused_signal.set(false);
+        new_chip8.set_speed(current_speed());
+        match new_chip8.start(&game, &mut display_signal, &mut lifecycle_signal) {
+            Ok(()) => {
+                error_signal.set(None);
+                chip8_signal.set(Some(new_chip8));
+                active_game_signal.set(Some(game.clone()));
+            },
+            Err(e) => {
+                error_signal.set(Some(format!("Could not start {}: {}", game.name, e)));
+                chip8_signal.set(None);
+                active_game_signal.set(None);
+            },
+        }
     };
 
     let mut pause_resume_emu = move ||{
         if let Some(chip8) = chip8_signal.write().as_mut(){
-            let is_paused = *paused_signal.peek();
-
-            if is_paused{
+            if chip8.lifecycle() == Lifecycle::Paused{
                 chip8.resume();
             }else{
                 chip8.pause();
             }
+        }
+    };
+
+    // Shift+click clears memory as well
+    let mut reset_emu = move |hard: bool|{
+        if let Some(chip8) = chip8_signal.write().as_mut(){
+            if hard{
+                chip8.hard_reset();
+            }else{
+                chip8.reset();
+            }
+            // Blended frames from before the reset would show through
+            display_signal.write().clear();
+        }
+    };
+
+    let apply_speed = move ||{
+        if let Some(chip8) = chip8_signal.peek().as_ref(){
+            chip8.set_speed(current_speed());
+        }
+    };
+
+    let mut toggle_fast_forward = move ||{
+        let enabled = *fast_forward_signal.peek();
+        fast_forward_signal.set(!enabled);
+        apply_speed();
+    };
 
-            paused_signal.set(!is_paused);
+    // Cycles 1x, 0.5x, 0.25x
+    let mut change_slow_motion = move ||{
+        let speed = match *slow_motion_signal.peek() {
+            speed if speed > 0.5 => 0.5,
+            speed if speed > 0.25 => 0.25,
+            _ => 1.0,
+        };
+        slow_motion_signal.set(speed);
+        apply_speed();
+    };
+
+    let take_screenshot = move ||{
+        if let (Some(chip8), Some(game)) = (chip8_signal.peek().as_ref(), active_game_signal.peek().as_ref()){
+            let screenshot = chip8.screenshot(&game.colors.palette(), SCREENSHOT_SCALE);
+            if let Ok(png) = screenshot.to_png(){
+                let file_name = format!("{}.png", game.name.to_lowercase().replace(' ', "_"));
+                download_file(&file_name, &png, "image/png");
+            }
         }
     };
 
     let mut handle_key_press = move |key: &String, pressed: bool|{
         if let Some(chip8) = chip8_signal.write().as_mut() {
-            chip8.handle_key_press(key, pressed);
+            let key_map = KEY_MAP.peek();
+            chip8.handle_key_press(key, pressed, &key_map);
         }
     };
 
@@ -59,6 +124,19 @@ pub fn Emulator() -> Element{
         }
     });
 
+    use_future(move || async move {
+        let mut gamepad_keys = [false; 16];
+        loop {
+            if let Some(game) = active_game_signal.peek().as_ref() {
+                for (key, pressed) in poll_gamepads(&game.gamepad_mapping(), &mut gamepad_keys) {
+                    let key_name = KEY_MAP.peek().get_key(key).to_string();
+                    handle_key_press(&key_name, pressed);
+                }
+            }
+            TimeoutFuture::new(16).await;
+        }
+    });
+
     rsx! {
         div{
             tabindex: "0",
@@ -67,9 +145,16 @@ pub fn Emulator() -> Element{
             div {
                 class: "flex justify-center",
                 EmuDisplay {
-                    display: display_signal(),
+                    frames: display_signal(),
                     game: active_game_signal(),
-                    paused: paused_signal()
+                    lifecycle: lifecycle_signal()
+                }
+            }
+            if let Some(error) = error_signal() {
+                div{
+                    role: "alert",
+                    class: "alert alert-error mt-2 text-xl",
+                    "{error}"
                 }
             }
             div{
@@ -100,7 +185,7 @@ pub fn Emulator() -> Element{
                 button{
                     onclick: move |_| pause_resume_emu(),
                     class: "btn btn-primary mt-5 text-xl flex-1",
-                    if paused_signal(){
+                    if lifecycle_signal() == Lifecycle::Paused{
                         span{
                             class: "text-3xl",
                             svg {
@@ -126,6 +211,47 @@ pub fn Emulator() -> Element{
                         }
                     }
                 }
+                button{
+                    onclick: move |event| reset_emu(event.modifiers().shift()),
+                    class: "btn btn-primary mt-5 text-xl font-thin flex-2",
+                    title: "Reset (Shift+click for a hard reset)",
+                    "Reset"
+                }
+                button{
+                    onclick: move |_| change_slow_motion(),
+                    class: "btn btn-primary mt-5 text-xl flex-1",
+                    class: if slow_motion_signal() < 1.0 { "btn-secondary" },
+                    title: "Slow motion",
+                    "{slow_motion_signal()}×"
+                }
+                button{
+                    onclick: move |_| toggle_fast_forward(),
+                    class: "btn btn-primary mt-5 text-xl flex-1",
+                    class: if fast_forward_signal() { "btn-secondary" },
+                    title: "Fast-forward",
+                    svg {
+                        xmlns: "http://www.w3.org/2000/svg",
+                        height: "24px",
+                        view_box: "0 -960 960 960",
+                        path {
+                            d: "M100-240v-480l360 240-360 240Zm400 0v-480l360 240-360 240Z"
+                        }
+                    }
+                }
+                button{
+                    onclick: move |_| take_screenshot(),
+                    class: "btn btn-primary mt-5 text-xl flex-1",
+                    title: "Save screenshot",
+                    disabled: active_game_signal().is_none(),
+                    svg {
+                        xmlns: "http://www.w3.org/2000/svg",
+                        height: "24px",
+                        view_box: "0 -960 960 960",
+                        path {
+                            d: "M480-260q75 0 127.5-52.5T660-440q0-75-52.5-127.5T480-620q-75 0-127.5 52.5T300-440q0 75 52.5 127.5T480-260Zm0-80q-42 0-71-29t-29-71q0-42 29-71t71-29q42 0 71 29t29 71q0 42-29 71t-71 29ZM160-120q-33 0-56.5-23.5T80-200v-480q0-33 23.5-56.5T160-760h126l74-80h240l74 80h126q33 0 56.5 23.5T880-680v480q0 33-23.5 56.5T800-120H160Zm0-80h640v-480H638l-73-80H395l-73 80H160v480Zm320-240Z"
+                        }
+                    }
+                }
                 button{
                     class: if !SHOW_KEYBOARD() {"lg:hidden"},
                     class: "btn btn-primary mt-5 text-xl flex-1",
@@ -144,6 +270,38 @@ pub fn Emulator() -> Element{
                     }
                 }
             }
+            div{
+                class: "flex flex-row justify-end items-center gap-2 mt-2",
+                span{
+                    class: "text-xl",
+                    "Keyboard layout"
+                }
+                select{
+                    name: "keyboard layout",
+                    class: "select select-sm select-primary text-xl w-auto",
+                    value: "{KEY_MAP().layout().name()}",
+                    onchange: move |event| {
+                        let value = event.value();
+                        if let Some(layout) = Layout::presets().into_iter().find(|layout| layout.name() == value){
+                            let key_map = KeyMap::new(layout);
+                            save_key_map(&key_map);
+                            *KEY_MAP.write() = key_map;
+                        }
+                    },
+                    for layout in Layout::presets(){
+                        option{
+                            value: "{layout.name()}",
+                            "{layout.name()}"
+                        }
+                    }
+                    if matches!(KEY_MAP().layout(), Layout::Custom{..}){
+                        option{
+                            value: "Custom",
+                            "Custom"
+                        }
+                    }
+                }
+            }
             if let Some(game) = active_game_signal() {
                 if !SHOW_KEYBOARD() {
                     Instructions{
@@ -1,10 +1,12 @@
 use dioxus::prelude::*;
 use crate::helpers::chip8_wrapper::Chip8Web;
 use crate::helpers::game::Game;
-use crate::SHOW_KEYBOARD;
+use crate::{KEY_MAP, SHOW_KEYBOARD};
 
 #[component]
 pub fn Instructions(game: Game) -> Element{
+    let key_map = KEY_MAP();
+
     rsx![
         div{
             class: "flex flex-col items-center mt-4",
@@ -25,7 +27,7 @@ pub fn Instructions(game: Game) -> Element{
                                     class: "kbd whitespace-nowrap ",
                                     span{
                                         class: "text-primary text-xl",
-                                        "{key.to_keyboard_str()}"
+                                        "{key_map.get_key_label(key)}"
                                     }
                                     span{
                                         class: "text-xl ml-2",
@@ -2,7 +2,7 @@ use dioxus::prelude::*;
 use chip8_lib::keypad::KeyPad;
 use crate::helpers::chip8_wrapper::Chip8Web;
 use crate::helpers::game::Game;
-use crate::KEYBOARD_EVENTS;
+use crate::{KEYBOARD_EVENTS, KEY_MAP};
 
 #[component]
 pub fn MobileKeyboard(game: Option<Game>) -> Element {
@@ -33,19 +33,19 @@ pub fn MobileKeyboard(game: Option<Game>) -> Element {
                         class: "text-3xl select-none btn btn-primary w-full active:btn-secondary",
                         onpointerdown: move |event| {
                             event.prevent_default();
-                            keyboard_events.set(Some((key.to_keyboard_str().to_string(), true)));
+                            keyboard_events.set(Some((KEY_MAP.peek().get_key(key).to_string(), true)));
                         },
                         onpointerleave: move |event| {
                             event.prevent_default();
-                            keyboard_events.set(Some((key.to_keyboard_str().to_string(), false)));
+                            keyboard_events.set(Some((KEY_MAP.peek().get_key(key).to_string(), false)));
                         },
                         onpointerup: move |event| {
                             event.prevent_default();
-                            keyboard_events.set(Some((key.to_keyboard_str().to_string(), false)));
+                            keyboard_events.set(Some((KEY_MAP.peek().get_key(key).to_string(), false)));
                         },
                         onpointercancel: move |event| {
                             event.prevent_default();
-                            keyboard_events.set(Some((key.to_keyboard_str().to_string(), false)));
+                            keyboard_events.set(Some((KEY_MAP.peek().get_key(key).to_string(), false)));
                         },
                         {key.to_chip8_str()}
                     }
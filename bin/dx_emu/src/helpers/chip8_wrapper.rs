@@ -1,51 +1,60 @@
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::time::Duration;
 use dioxus::dioxus_core::Task;
-use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::chip_8::{Chip8, Lifecycle, Mode, MAX_TICK_LAG_NS};
 use dioxus::prelude::*;
-use chip8_lib::display::Display;
+use chip8_lib::frame_composer::FrameComposer;
 use crate::helpers::game::Game;
 use web_time::Instant;
 use gloo_timers::future::TimeoutFuture;
-use chip8_lib::keypad::KeyPad;
+use chip8_lib::keymap::KeyMap;
+use chip8_lib::palette::Palette;
+use chip8_lib::screenshot::Screenshot;
+use chip8_lib::sound::TICK_NS;
 
+// The browser has no threads for the machine, a task runs its ticks instead.
+// Tasks only touch the machine between awaits, so the RefCell is never borrowed twice.
 pub struct Chip8Web{
-    chip8: Chip8,
-    timer_thread: Option<Task>,
+    chip8: Rc<RefCell<Chip8>>,
     execution_thread: Option<Task>,
     display_thread: Option<Task>,
-    ipf_before_pause: u32,
-    fps_before_pause: u64,
+    display_signal: Option<Signal<FrameComposer>>,
+    lifecycle_signal: Option<Signal<Lifecycle>>,
 }
 
 impl Chip8Web {
     pub fn new(mode: Mode) -> Chip8Web {
-        let chip8 = Chip8::new(mode);
-        let ipf_before_pause = chip8.ipf.load(Ordering::Relaxed);
-        
         Chip8Web{
-            chip8,
+            chip8: Rc::new(RefCell::new(Chip8::new(mode))),
             execution_thread: None,
-            timer_thread: None,
             display_thread: None,
-            ipf_before_pause,
-            fps_before_pause: 0
+            display_signal: None,
+            lifecycle_signal: None,
         }
     }
-    pub fn start(&mut self, game: &Game, display_signal: &mut Signal<Display>){
-        self.chip8.load_font_into_memory();
-        self.load_cartridge(game.bytes);
-        self.start_timer_thread();
+    pub fn start(&mut self, game: &Game, display_signal: &mut Signal<FrameComposer>, lifecycle_signal: &mut Signal<Lifecycle>) -> Result<(), String>{
+        self.display_signal = Some(*display_signal);
+        self.lifecycle_signal = Some(*lifecycle_signal);
+        let mut chip8 = self.chip8.borrow_mut();
+        chip8.load_font_into_memory();
+        chip8.load_rom(game.bytes)?;
+        drop(chip8);
+        self.start_threads();
+        Ok(())
+    }
+
+    fn start_threads(&mut self){
+        *self.chip8.borrow().lifecycle.lock().unwrap() = Lifecycle::Running;
         self.start_execution_thread();
-        self.start_display_thread(display_signal)
+        self.start_display_thread();
     }
 
     pub fn stop(&mut self) {
-        self.chip8.running.store(false, Ordering::Relaxed);
+        self.chip8.borrow_mut().stop();
 
-        if let Some(timer_thread) = self.timer_thread.take() {
-            timer_thread.cancel();
-        }
         if let Some(execution_thread) = self.execution_thread.take() {
             execution_thread.cancel();
         }
@@ -54,58 +63,26 @@ impl Chip8Web {
         }
     }
 
-    fn start_timer_thread(&mut self){
-        let state = Arc::clone(&self.chip8.state);
-        let running = Arc::clone(&self.chip8.running);
-        let fps_ns = Arc::clone(&self.chip8.fps_ns);
-
-        let timer_thread = spawn(async move {
-            while running.load(Ordering::Relaxed) {
-                let start = Instant::now();
-
-                if let Ok(mut cpu_state) = state.lock() {
-                    cpu_state.delay_timer = cpu_state.delay_timer.saturating_sub(1);
-                    cpu_state.sound_timer = cpu_state.sound_timer.saturating_sub(1);
-                }
-
-                let elapsed_ns = start.elapsed().as_nanos() as u64;
-                let wait_time_ns = fps_ns.load(Ordering::Relaxed).saturating_sub(elapsed_ns);
-
-                Self::delay(wait_time_ns).await;
-            }
-
-        });
-
-        self.timer_thread = Some(timer_thread);
-    }
-
+    // Browsers clamp timeouts to a few ms, so every wakeup runs all ticks that are due by then,
+    // pausing and halting are left to the machine. After a long stall the clock starts over.
     fn start_execution_thread(&mut self){
-        let display = Arc::clone(&self.chip8.display);
-        let state = Arc::clone(&self.chip8.state);
-        let running = Arc::clone(&self.chip8.running);
-        let keys = Arc::clone(&self.chip8.keys);
-        let hires_mode = Arc::clone(&self.chip8.hires_mode);
-        let ipf = Arc::clone(&self.chip8.ipf);
-        let fps_ns = Arc::clone(&self.chip8.fps_ns);
+        let chip8 = Rc::clone(&self.chip8);
+        let running = Arc::clone(&self.chip8.borrow().running);
 
         let execution_thread = spawn(async move {
+            let mut next_tick = Instant::now();
             while running.load(Ordering::Relaxed) {
-                let start = Instant::now();
-                {
-                    let mut cpu_state = state.lock().unwrap();
-                    let mut display = display.lock().unwrap();
-                    let keys = keys.lock().unwrap();
-
-                    for _ in 0..ipf.load(Ordering::Relaxed){
-                        if let Some(instruction) = cpu_state.get_current_instruction(true){
-                            instruction.execute(&mut cpu_state, &mut display, &keys, hires_mode.as_ref(), running.as_ref());
-                        }
-
-                    }
+                let now = Instant::now();
+                if now.saturating_duration_since(next_tick) > Duration::from_nanos(MAX_TICK_LAG_NS) {
+                    next_tick = now;
+                }
 
+                while next_tick <= now && running.load(Ordering::Relaxed) {
+                    chip8.borrow().run_frames(1);
+                    next_tick += Duration::from_nanos(Chip8::get_tick_interval_ns(&chip8.borrow().speed).max(1));
                 }
-                let elapsed_ns = start.elapsed().as_nanos() as u64;
-                let wait_time_ns = fps_ns.load(Ordering::Relaxed).saturating_sub(elapsed_ns);
+
+                let wait_time_ns = next_tick.saturating_duration_since(Instant::now()).as_nanos() as u64;
                 Self::delay(wait_time_ns).await;
             }
         });
@@ -113,97 +90,100 @@ impl Chip8Web {
         self.execution_thread = Some(execution_thread);
     }
 
-    fn start_display_thread(&mut self, display_signal: &mut Signal<Display>){
-        let fps_ns = Arc::clone(&self.chip8.fps_ns);
-        let chip8_display = Arc::clone(&self.chip8.display);
-        let running = Arc::clone(&self.chip8.running);
-        let mut display_signal = display_signal.clone();
+    fn start_display_thread(&mut self){
+        let (Some(mut display_signal), Some(mut lifecycle_signal)) = (self.display_signal, self.lifecycle_signal) else {
+            return;
+        };
+        let chip8 = Rc::clone(&self.chip8);
+        let running = Arc::clone(&self.chip8.borrow().running);
 
         let display_thread = spawn(async move {
             while running.load(Ordering::Relaxed) {
                 let start = Instant::now();
 
-                if let Ok(display) = chip8_display.lock(){
-                    display_signal.set(*display);
+                Self::push_frame(&chip8.borrow(), &mut display_signal);
+                let lifecycle = chip8.borrow().get_lifecycle();
+                if *lifecycle_signal.peek() != lifecycle {
+                    lifecycle_signal.set(lifecycle);
                 }
 
                 let elapsed_ns = start.elapsed().as_nanos() as u64;
-                let wait_time_ns = fps_ns.load(Ordering::Relaxed).saturating_sub(elapsed_ns);
+                let wait_time_ns = TICK_NS.saturating_sub(elapsed_ns);
                 Self::delay(wait_time_ns).await;
             }
 
+            // The last frame before halting, and the reason
+            Self::push_frame(&chip8.borrow(), &mut display_signal);
+            lifecycle_signal.set(chip8.borrow().get_lifecycle());
         });
 
+        lifecycle_signal.set(self.lifecycle());
         self.display_thread = Some(display_thread);
     }
 
-    fn load_cartridge(&mut self, rom_bytes: &[u8]){
-        let mut state = self.chip8.state.lock().unwrap();
-        for (i, byte) in rom_bytes.iter().enumerate(){
-            state.memory[0x200 + i] = *byte;
+    // Only new ticks are pushed, so frames shown twice aren't combined with themselves
+    fn push_frame(chip8: &Chip8, display_signal: &mut Signal<FrameComposer>){
+        let (tick, display) = chip8.get_frame();
+        if display_signal.peek().last_tick() != Some(tick) {
+            display_signal.write().push(tick, display);
         }
     }
-    pub fn handle_key_press(&mut self, key: &String, pressed: bool){
-        if let Some(key) = Self::get_keypad(&key){
-            self.chip8.handle_input(key, pressed);
+
+    pub fn handle_key_press(&mut self, key: &str, pressed: bool, key_map: &KeyMap){
+        if let Some(key) = key_map.get_keypad(key){
+            self.chip8.borrow_mut().handle_input(key, pressed);
         }
     }
 
+    pub fn lifecycle(&self) -> Lifecycle{
+        self.chip8.borrow().get_lifecycle()
+    }
+
+    pub fn set_speed(&self, speed: f32){
+        self.chip8.borrow().set_speed(speed);
+    }
+
+    pub fn screenshot(&self, palette: &Palette, scale: u32) -> Screenshot{
+        self.chip8.borrow().screenshot(palette, scale)
+    }
+
     pub fn pause(&mut self){
-        self.ipf_before_pause = self.chip8.ipf.load(Ordering::Relaxed);
-        self.chip8.ipf.store(0, Ordering::Relaxed);
+        self.chip8.borrow_mut().pause();
+        self.update_lifecycle_signal();
     }
-    
+
     pub fn resume(&mut self){
-        self.chip8.ipf.store(self.ipf_before_pause, Ordering::Relaxed);
-    }
-
-    fn get_keypad(key: &String) -> Option<KeyPad> {
-        let mut key = key.clone();
-        key = key.to_lowercase();
-
-        match key.as_str() {
-            "1" => Some(KeyPad::Num1),
-            "2" => Some(KeyPad::Num2),
-            "3" => Some(KeyPad::Num3),
-            "4" => Some(KeyPad::C),
-            "q" => Some(KeyPad::Num4),
-            "w" => Some(KeyPad::Num5),
-            "e" => Some(KeyPad::Num6),
-            "r" => Some(KeyPad::D),
-            "a" => Some(KeyPad::Num7),
-            "s" => Some(KeyPad::Num8),
-            "d" => Some(KeyPad::Num9),
-            "f" => Some(KeyPad::E),
-            "z" => Some(KeyPad::A),
-            "x" => Some(KeyPad::Num0),
-            "c" => Some(KeyPad::B),
-            "v" => Some(KeyPad::F),
-            _ => None,
+        self.chip8.borrow_mut().resume();
+        self.update_lifecycle_signal();
+    }
+
+    pub fn reset(&mut self){
+        self.restart(Chip8::reset);
+    }
+
+    pub fn hard_reset(&mut self){
+        self.restart(Chip8::hard_reset);
+    }
+
+    // The tasks end with the machine, so they are started again after resetting it
+    fn restart(&mut self, reset: fn(&mut Chip8)){
+        let paused = self.lifecycle() == Lifecycle::Paused;
+        self.stop();
+        reset(&mut self.chip8.borrow_mut());
+        self.start_threads();
+        if paused {
+            self.chip8.borrow_mut().pause();
         }
+        self.update_lifecycle_signal();
     }
 
-    pub fn chip8_key_to_keyboard(key: &str) -> &str{
-        match key{
-            "1" => "1",
-            "2" => "2",
-            "3" => "3",
-            "C" => "4",
-            "4" => "Q",
-            "5" => "W",
-            "6" => "E",
-            "D" => "R",
-            "7" => "A",
-            "8" => "S",
-            "9" => "D",
-            "E" => "F",
-            "A" => "Z",
-            "0" => "X",
-            "B" => "C",
-            "F" => "V",
-            _ => "❌"
+    fn update_lifecycle_signal(&mut self){
+        let lifecycle = self.lifecycle();
+        if let Some(lifecycle_signal) = self.lifecycle_signal.as_mut() {
+            lifecycle_signal.set(lifecycle);
         }
     }
+
     async fn delay(ns: u64) {
         TimeoutFuture::new((ns as f64 / 1_000_000f64).round() as u32).await;
     }
@@ -0,0 +1,19 @@
+use web_sys::js_sys::{Array, Uint8Array};
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+// Hands the bytes to the browser as a file download through a temporary object URL
+pub fn download_file(file_name: &str, bytes: &[u8], mime_type: &str) -> Option<()>{
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&Array::of1(&Uint8Array::from(bytes)), &options).ok()?;
+    let url = Url::create_object_url_with_blob(&blob).ok()?;
+
+    let document = web_sys::window()?.document()?;
+    let anchor = document.create_element("a").ok()?.dyn_into::<HtmlAnchorElement>().ok()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    Url::revoke_object_url(&url).ok()
+}
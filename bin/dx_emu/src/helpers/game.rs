@@ -1,6 +1,9 @@
 use dioxus::prelude::*;
 use chip8_lib::chip_8::Mode;
+use chip8_lib::frame_composer::FlickerFilter;
 use chip8_lib::keypad::KeyPad;
+use chip8_lib::palette::Palette;
+use crate::helpers::gamepad::{DPAD_DOWN, DPAD_LEFT, DPAD_RIGHT, DPAD_UP, FACE_BUTTONS};
 
 #[derive(Copy, Clone, PartialEq)]
 pub struct Colors{
@@ -9,6 +12,12 @@ pub struct Colors{
     pub mixed: &'static str,
     pub none: &'static str,
 }
+
+impl Colors{
+    pub fn palette(&self) -> Palette{
+        Palette::from_hex(&[self.none, self.plane1, self.plane2, self.mixed]).expect("Invalid game colors")
+    }
+}
 #[derive(Clone, PartialEq)]
 pub struct ControlsGroup{
     pub name: &'static str,
@@ -28,6 +37,7 @@ pub struct Game{
     pub bytes: &'static [u8],
     pub mode: Mode,
     pub colors: Colors,
+    pub flicker: FlickerFilter,
     pub instructions: Vec<ControlsGroup>,
     pub author: Option<Author>,
 }
@@ -40,6 +50,23 @@ impl Game{
         self.instructions.iter().flat_map(|group| group.controls.iter().cloned()).collect()
     }
 
+    /// Gamepad button index to keypad key: Up/Down/Left/Right controls go on the D-pad,
+    /// the remaining ones on the face buttons and shoulders in the order they are listed.
+    pub fn gamepad_mapping(&self) -> Vec<(usize, KeyPad)>{
+        let mut face_buttons = FACE_BUTTONS.into_iter();
+
+        self.get_all_controls().into_iter().filter_map(|(key, action)| {
+            let button = match action.to_lowercase().as_str() {
+                "up" => Some(DPAD_UP),
+                "down" => Some(DPAD_DOWN),
+                "left" => Some(DPAD_LEFT),
+                "right" => Some(DPAD_RIGHT),
+                _ => face_buttons.next(),
+            };
+            button.map(|button| (button, key))
+        }).collect()
+    }
+
     pub fn br8kout() -> Game{
         Game{
             name: "Br8kout",
@@ -51,6 +78,7 @@ impl Game{
                 mixed: "#000000",
                 none: "#000000"
             },
+            flicker: FlickerFilter::Or{ frames: 2 },
             instructions: vec![
                 ControlsGroup{
                     name: "Steering",
@@ -78,6 +106,7 @@ impl Game{
                 mixed: "#EEEEFF",
                 none: "#87CEEB"
             },
+            flicker: FlickerFilter::Off,
             instructions: vec![
                 ControlsGroup{
                     name: "Steering",
@@ -118,6 +147,7 @@ impl Game{
                 mixed: "#EEEEFF",
                 none: "#FAD5A5"
             },
+            flicker: FlickerFilter::Off,
             instructions: vec![
                 ControlsGroup{
                     name: "Steering",
@@ -153,6 +183,7 @@ impl Game{
                 mixed: "#662200",
                 none: "#113152"
             },
+            flicker: FlickerFilter::Or{ frames: 2 },
             instructions: vec![
                 ControlsGroup{
                     name: "Steering",
@@ -188,6 +219,7 @@ impl Game{
                 mixed: "#000000",
                 none: "#000000"
             },
+            flicker: FlickerFilter::Off,
             instructions: vec![
                 ControlsGroup{
                     name: "Actions",
@@ -0,0 +1,66 @@
+use chip8_lib::keypad::KeyPad;
+use web_sys::js_sys::Array;
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+// Button indexes of the "standard" Gamepad API mapping
+pub const FACE_BUTTONS: [usize; 8] = [0, 1, 2, 3, 4, 5, 6, 7]; // A, B, X, Y, LB, RB, LT, RT
+pub const DPAD_UP: usize = 12;
+pub const DPAD_DOWN: usize = 13;
+pub const DPAD_LEFT: usize = 14;
+pub const DPAD_RIGHT: usize = 15;
+const BUTTON_COUNT: usize = 16;
+const STICK_THRESHOLD: f64 = 0.5;
+
+/// Polls every connected gamepad and returns the keypad keys that were pressed or released since the last call.
+/// A key stays pressed while any gamepad holds a button mapped to it.
+pub fn poll_gamepads(mapping: &[(usize, KeyPad)], previous_keys: &mut [bool; 16]) -> Vec<(KeyPad, bool)>{
+    let mut buttons = [false; BUTTON_COUNT];
+    for gamepad in connected_gamepads() {
+        for (index, pressed) in read_buttons(&gamepad).into_iter().enumerate() {
+            buttons[index] |= pressed;
+        }
+    }
+
+    let mut keys = [false; 16];
+    for (button, key) in mapping {
+        if buttons.get(*button).copied().unwrap_or(false) {
+            keys[*key as usize] = true;
+        }
+    }
+
+    let changes = KeyPad::all().into_iter()
+        .filter(|key| keys[*key as usize] != previous_keys[*key as usize])
+        .map(|key| (key, keys[key as usize]))
+        .collect();
+    *previous_keys = keys;
+    changes
+}
+
+fn connected_gamepads() -> Vec<Gamepad>{
+    let Some(window) = web_sys::window() else { return vec![] };
+    let Ok(gamepads) = window.navigator().get_gamepads() else { return vec![] };
+
+    gamepads.iter()
+        .filter_map(|gamepad| gamepad.dyn_into::<Gamepad>().ok())
+        .filter(|gamepad| gamepad.connected())
+        .collect()
+}
+
+// The left stick is read as a second D-pad
+fn read_buttons(gamepad: &Gamepad) -> [bool; BUTTON_COUNT]{
+    let mut buttons = [false; BUTTON_COUNT];
+    for (index, button) in gamepad.buttons().iter().take(BUTTON_COUNT).enumerate() {
+        buttons[index] = button.dyn_into::<GamepadButton>().map(|button| button.pressed()).unwrap_or(false);
+    }
+
+    let axes: Array = gamepad.axes();
+    let horizontal = axes.get(0).as_f64().unwrap_or(0.0);
+    let vertical = axes.get(1).as_f64().unwrap_or(0.0);
+    buttons[DPAD_LEFT] |= horizontal < -STICK_THRESHOLD;
+    buttons[DPAD_RIGHT] |= horizontal > STICK_THRESHOLD;
+    buttons[DPAD_UP] |= vertical < -STICK_THRESHOLD;
+    buttons[DPAD_DOWN] |= vertical > STICK_THRESHOLD;
+
+    buttons
+}
@@ -1,2 +1,5 @@
 pub mod chip8_wrapper;
-pub mod game;
\ No newline at end of file
+pub mod download;
+pub mod game;
+pub mod gamepad;
+pub mod storage;
\ No newline at end of file
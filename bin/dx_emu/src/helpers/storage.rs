@@ -0,0 +1,21 @@
+use chip8_lib::keymap::KeyMap;
+
+const KEY_MAP_STORAGE_KEY: &str = "chip8_key_map";
+
+fn local_storage() -> Option<web_sys::Storage>{
+    web_sys::window()?.local_storage().ok()?
+}
+
+// Stored as JSON, e.g. {"layout":"azerty"} or {"layout":"custom","keys":["x","1",...]}
+pub fn load_key_map() -> KeyMap{
+    local_storage()
+        .and_then(|storage| storage.get_item(KEY_MAP_STORAGE_KEY).ok()?)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_key_map(key_map: &KeyMap){
+    if let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(key_map)) {
+        let _ = storage.set_item(KEY_MAP_STORAGE_KEY, &json);
+    }
+}
@@ -6,6 +6,7 @@ use dioxus::prelude::*;
 use web_sys::wasm_bindgen::closure::Closure;
 use web_sys::wasm_bindgen::JsCast;
 use chip8_lib::chip_8::{Mode};
+use chip8_lib::keymap::KeyMap;
 use views::{Home};
 //use crate::release_materials::NumpadGraphic;
 
@@ -34,6 +35,7 @@ const GLOBAL_CSS: Asset = asset!("/assets/global.css");
 const JERSEY10_FONT: Asset = asset!("/assets/fonts/Jersey10-Regular.ttf");
 static KEYBOARD_EVENTS: GlobalSignal<Option<(String, bool)>> = GlobalSignal::new(|| None);
 static SHOW_KEYBOARD: GlobalSignal<bool> = GlobalSignal::new(|| false);
+static KEY_MAP: GlobalSignal<KeyMap> = GlobalSignal::new(helpers::storage::load_key_map);
 
 fn main() {
     dioxus::launch(App);
@@ -51,11 +53,11 @@ fn App() -> Element {
         let window = web_sys::window().unwrap();
 
         let closure_keydown = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
-            keyboard_events.set(Some((event.key(), true)));
+            keyboard_events.set(Some((key_name(&event), true)));
         }) as Box<dyn FnMut(_)>);
 
         let closure_keyup = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
-            keyboard_events.set(Some((event.key(), false)));
+            keyboard_events.set(Some((key_name(&event), false)));
         }) as Box<dyn FnMut(_)>);
 
         window.add_event_listener_with_callback("keydown", closure_keydown.as_ref().unchecked_ref()).unwrap();
@@ -89,3 +91,17 @@ fn App() -> Element {
     }
 }
 
+/// Translates a browser keyboard event into a [`KeyMap`] key name. Numeric keypad keys are told apart
+/// by their code, so "Numpad7" becomes "kp7" while the "7" above the letters stays "7".
+fn key_name(event: &web_sys::KeyboardEvent) -> String {
+    let code = event.code();
+    match code.strip_prefix("Numpad") {
+        Some("Divide") => "kp/".to_string(),
+        Some("Multiply") => "kp*".to_string(),
+        Some("Subtract") => "kp-".to_string(),
+        Some("Add") => "kp+".to_string(),
+        Some("Decimal") => "kp.".to_string(),
+        Some(numpad_key) => format!("kp{}", numpad_key.to_lowercase()),
+        None => event.key().to_lowercase(),
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use chip8_lib::args::{next_value, parse_number};
+use chip8_lib::chip_8::{Mode, MAX_SPEED, MIN_SPEED, UNCAPPED_SPEED};
+use chip8_lib::cpu_state::Quirk;
+use chip8_lib::frame_composer::FlickerFilter;
+use chip8_lib::palette::Palette;
+use chip8_lib::parameters::PIXEL_SIZE;
+use chip8_lib::sound::DEFAULT_VOLUME;
+use crate::video::screen::ScaleMode;
+
+pub const USAGE: &str = "Usage: sdl_emu [ROM] [OPTIONS]
+
+Opens a file picker when no ROM is given.
+
+Options:
+  --mode <MODE>           chip8, schip, xochip or experimental (default: chip8)
+  --ipf <N>               instructions per frame (default: depends on mode)
+  --fps <N>               frames drawn per second, the machine itself always runs at 60 Hz (default: 60)
+  --vsync                 draw frames in sync with the monitor instead of at --fps
+  --speed <X>             emulation speed multiplier, e.g. 0.5 or 2 (default: 1)
+  --fast-forward <X>      speed while Tab is held, a multiplier or uncapped (default: 4)
+  --scale <N>             window pixel size (default: 8)
+  --scaling <MODE>        integer or fit, how the display fills the window (default: integer)
+  --palette <PALETTE>     sky, mono, amber, lcd or four hex colors:
+                          background,plane1,plane2,mixed
+  --quirk <NAME>=<on|off> override a quirk, can be repeated
+                          (vf-reset, shift, jump, memory, i-overflow, wrap)
+  --flicker <FILTER>      off, or, blend, optionally with a frame count (e.g. or:3),
+                          combines the last frames to reduce sprite flicker
+  --fullscreen            start in fullscreen (F11 toggles it)
+  --screenshot-scale <N>  pixel size of F12 screenshots and F9 recordings, 1 saves
+                          screenshots at native 64x32 or 128x64 resolution (default: 1)
+  --mute                  start with sound muted (M toggles it)
+  --volume <N>            master volume in percent, 0 to 100 (default: 20)
+  --config <FILE>         config file (default: sdl_emu.toml if present)
+  --record <FILE>         record keypad input into a movie file
+  --play <FILE>           play keypad input back from a movie file
+  -h, --help              print this message";
+
+#[derive(Debug)]
+pub struct Options{
+    pub rom: Option<PathBuf>,
+    pub mode: Mode,
+    pub ipf: Option<u32>,
+    pub fps: u16,
+    pub vsync: bool,
+    pub speed: f32,
+    pub fast_forward: f32,
+    pub scale: u32,
+    pub scaling: ScaleMode,
+    pub palette: Palette,
+    pub quirks: Vec<(Quirk, bool)>,
+    pub flicker: Option<FlickerFilter>,
+    pub fullscreen: bool,
+    pub screenshot_scale: u32,
+    pub mute: bool,
+    pub volume: f32,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options{
+            rom: None,
+            mode: Mode::Chip8,
+            ipf: None,
+            fps: 60,
+            vsync: false,
+            speed: 1.0,
+            fast_forward: 4.0,
+            scale: PIXEL_SIZE,
+            scaling: ScaleMode::Integer,
+            palette: Palette::default(),
+            quirks: vec![],
+            flicker: None,
+            fullscreen: false,
+            screenshot_scale: 1,
+            mute: false,
+            volume: DEFAULT_VOLUME,
+            record: None,
+            play: None,
+            config: None,
+            help: false,
+        }
+    }
+}
+
+impl Options{
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String>{
+        let mut options = Options::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--mode" => options.mode = next_value(&mut args, &arg)?.parse()?,
+                "--ipf" => options.ipf = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+                "--fps" => options.fps = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                "--vsync" => options.vsync = true,
+                "--speed" => options.speed = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                "--fast-forward" => options.fast_forward = parse_fast_forward(&next_value(&mut args, &arg)?)?,
+                "--scale" => options.scale = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                "--scaling" => options.scaling = next_value(&mut args, &arg)?.parse()?,
+                "--palette" => options.palette = parse_palette(&next_value(&mut args, &arg)?)?,
+                "--quirk" => options.quirks.push(Quirk::parse_setting(&next_value(&mut args, &arg)?)?),
+                "--flicker" => options.flicker = Some(next_value(&mut args, &arg)?.parse()?),
+                "--fullscreen" => options.fullscreen = true,
+                "--screenshot-scale" => options.screenshot_scale = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+                "--mute" => options.mute = true,
+                "--volume" => options.volume = parse_volume(&next_value(&mut args, &arg)?)?,
+                "--record" => options.record = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--play" => options.play = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                "--config" => options.config = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+                _ => {
+                    if options.rom.is_some() {
+                        return Err(format!("Only one ROM can be given\n\n{}", USAGE));
+                    }
+                    options.rom = Some(PathBuf::from(arg));
+                }
+            }
+        }
+
+        if options.record.is_some() && options.play.is_some() {
+            return Err("--record and --play cannot be used together".to_string());
+        }
+        let fast_forward_valid = options.fast_forward == UNCAPPED_SPEED || (MIN_SPEED..=MAX_SPEED).contains(&options.fast_forward);
+        if !(MIN_SPEED..=MAX_SPEED).contains(&options.speed) || !fast_forward_valid {
+            return Err(format!("--speed and --fast-forward must be between {} and {}", MIN_SPEED, MAX_SPEED));
+        }
+        if options.fps == 0 || options.scale == 0 || options.screenshot_scale == 0 {
+            return Err("--fps, --scale and --screenshot-scale must be greater than 0".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_fast_forward(value: &str) -> Result<f32, String>{
+    match value {
+        "uncapped" => Ok(UNCAPPED_SPEED),
+        _ => parse_number(value, "--fast-forward"),
+    }
+}
+
+fn parse_volume(value: &str) -> Result<f32, String>{
+    let percent: u8 = parse_number(value, "--volume")?;
+    if percent > 100 {
+        return Err(format!("Invalid value for --volume: {}", value));
+    }
+    Ok(percent as f32 / 100.0)
+}
+
+fn parse_palette(value: &str) -> Result<Palette, String>{
+    if let Some(palette) = Palette::from_name(value) {
+        return Ok(palette);
+    }
+
+    let colors: Vec<&str> = value.split(',').collect();
+    Palette::from_hex(&colors).ok_or(format!("Invalid palette: {}", value))
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use chip8_lib::frame_composer::FlickerFilter;
+use chip8_lib::keymap::KeyMap;
+use crate::controller::ControllerMap;
+
+// Looked up in the working directory when no --config is given
+pub const DEFAULT_CONFIG_FILE: &str = "sdl_emu.toml";
+
+// Example:
+//
+// [keymap]
+// layout = "azerty"      # qwerty, qwertz, azerty, numeric_keypad or custom
+//
+// [keymap]
+// layout = "custom"
+// keys = ["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", "v"]  # keys for 0 to F
+//
+// [flicker]              # anti-flicker frame blending for all ROMs, overridden by --flicker
+// filter = "or"          # off, or or blend
+// frames = 2             # how many frames are combined
+//
+// [controller]           # replaces the default controller mapping for all ROMs
+// dpup = "5"
+// a = "6"
+//
+// [roms."t8nks.ch8".controller]   # replaces it for one ROM, matched by file name
+// dpup = "5"
+// dpdown = "8"
+// dpleft = "7"
+// dpright = "9"
+// a = "6"
+// x = "1"
+// y = "2"
+// b = "3"
+//
+// [roms."br8kout.ch8".flicker]
+// filter = "blend"
+// frames = 3
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config{
+    pub keymap: KeyMap,
+    pub controller: Option<HashMap<String, String>>,
+    pub flicker: FlickerFilter,
+    pub roms: HashMap<String, RomConfig>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct RomConfig{
+    pub controller: Option<HashMap<String, String>>,
+    pub flicker: Option<FlickerFilter>,
+}
+
+impl Config{
+    pub fn load(path: Option<&PathBuf>) -> Result<Config, String>{
+        let path = match path {
+            Some(path) => path.as_path(),
+            None => {
+                let default_path = Path::new(DEFAULT_CONFIG_FILE);
+                if !default_path.exists() {
+                    return Ok(Config::default());
+                }
+                default_path
+            }
+        };
+
+        let content = fs::read_to_string(path).map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+        let config: Config = toml::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+        let controller_mappings = config.controller.iter().chain(config.roms.values().filter_map(|rom| rom.controller.as_ref()));
+        for mapping in controller_mappings {
+            ControllerMap::from_config(mapping).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+        }
+
+        Ok(config)
+    }
+
+    fn get_rom_config(&self, rom: &Path) -> Option<&RomConfig>{
+        let file_name = rom.file_name()?.to_str()?;
+        self.roms.get(file_name)
+    }
+
+    pub fn get_controller_map(&self, rom: &Path) -> ControllerMap{
+        let rom_mapping = self.get_rom_config(rom).and_then(|rom_config| rom_config.controller.as_ref());
+
+        match rom_mapping.or(self.controller.as_ref()) {
+            Some(mapping) => ControllerMap::from_config(mapping).unwrap_or_default(),
+            None => ControllerMap::default(),
+        }
+    }
+
+    pub fn get_flicker_filter(&self, rom: &Path) -> FlickerFilter{
+        self.get_rom_config(rom).and_then(|rom_config| rom_config.flicker).unwrap_or(self.flicker)
+    }
+}
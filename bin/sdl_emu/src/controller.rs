@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use sdl2::controller::{Button, GameController};
+use sdl2::event::Event;
+use sdl2::{GameControllerSubsystem, Sdl};
+use chip8_lib::keypad::KeyPad;
+
+pub struct ControllerMap{
+    buttons: HashMap<Button, KeyPad>,
+}
+
+impl Default for ControllerMap {
+    // Steering on the D-pad like most games (5/8/7/9), actions on face buttons and shoulders
+    fn default() -> ControllerMap {
+        let buttons = [
+            (Button::DPadUp, KeyPad::Num5),
+            (Button::DPadDown, KeyPad::Num8),
+            (Button::DPadLeft, KeyPad::Num7),
+            (Button::DPadRight, KeyPad::Num9),
+            (Button::A, KeyPad::Num6),
+            (Button::B, KeyPad::Num4),
+            (Button::X, KeyPad::Num1),
+            (Button::Y, KeyPad::Num2),
+            (Button::LeftShoulder, KeyPad::Num3),
+            (Button::RightShoulder, KeyPad::C),
+            (Button::Back, KeyPad::Num0),
+            (Button::Start, KeyPad::F),
+        ];
+
+        ControllerMap{ buttons: HashMap::from(buttons) }
+    }
+}
+
+impl ControllerMap{
+    // Button names as used by SDL ("a", "dpup", "leftshoulder", ...) to keypad keys ("0" to "F")
+    pub fn from_config(mapping: &HashMap<String, String>) -> Result<ControllerMap, String>{
+        let mut buttons = HashMap::new();
+
+        for (button_name, key_name) in mapping {
+            let button = Button::from_string(button_name).ok_or(format!("Unknown controller button: {}", button_name))?;
+            let key = KeyPad::from_chip8_str(key_name).ok_or(format!("Unknown keypad key: {}", key_name))?;
+            buttons.insert(button, key);
+        }
+
+        Ok(ControllerMap{ buttons })
+    }
+
+    pub fn get_keypad(&self, button: Button) -> Option<KeyPad>{
+        self.buttons.get(&button).copied()
+    }
+}
+
+pub struct Controllers{
+    subsystem: Option<GameControllerSubsystem>,
+    connected: HashMap<u32, GameController>, // by joystick instance id
+}
+
+impl Controllers{
+    pub fn new(context: &Sdl) -> Controllers{
+        let subsystem = context.game_controller().map_err(|e| println!("Game controllers unavailable: {}", e)).ok();
+
+        Controllers{
+            subsystem,
+            connected: HashMap::new(),
+        }
+    }
+
+    // Keeps track of plugged in controllers, SDL also reports the ones connected at startup as added
+    pub fn handle_device_event(&mut self, event: &Event){
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let Some(subsystem) = &self.subsystem else { return; };
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.connected.insert(controller.instance_id(), controller);
+                    }
+                    Err(e) => println!("Could not open controller: {}", e),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.connected.remove(&which) {
+                    println!("Controller disconnected: {}", controller.name());
+                }
+            }
+            _ => {}
+        }
+    }
+}
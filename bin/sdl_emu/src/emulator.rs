@@ -1,20 +1,30 @@
+use std::fs;
 use std::path::{PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use sdl2::render::WindowCanvas;
+use sdl2::render::{Texture, TextureCreator, WindowCanvas};
 use sdl2::{EventPump, Sdl};
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use std::time::{Duration, Instant};
-use chip8_lib::chip_8::{Chip8, Mode};
-use chip8_lib::display::Display;
-use chip8_lib::keypad::KeyPad;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::video::{FullscreenType, WindowContext};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use chip8_lib::chip_8::{Chip8, HaltReason, Lifecycle, Mode, UNCAPPED_SPEED};
+use chip8_lib::frame_composer::FrameComposer;
+use chip8_lib::keymap::{KeyMap, KeyNames};
+use chip8_lib::palette::Palette;
 use chip8_lib::parameters::*;
+use crate::cli::Options;
+use crate::config::Config;
+use crate::controller::{ControllerMap, Controllers};
 use crate::file_picker;
+use crate::movie::{MoviePlayer, MovieRecorder, MovieSettings};
+use crate::recording::{Recorder, RecordingFormat};
 use crate::sound::audio_manager::AudioManager;
+use crate::video::filters::Filters;
+use crate::video::osd::Osd;
+use crate::video::screen::Screen;
 
 extern crate sdl2;
 
@@ -23,56 +33,140 @@ pub struct Emulator {
     canvas: WindowCanvas,
     event_pump: EventPump,
     chip8: Chip8,
+    lifecycle: Lifecycle,
     current_game: PathBuf,
     fps: u16,
     fps_ns: u64,
-    audio_manager: AudioManager
+    speed: f32,
+    fast_forward: bool,
+    audio_manager: AudioManager,
+    options: Options,
+    palette: Palette,
+    screen: Screen,
+    frame_composer: FrameComposer,
+    key_map: KeyMap,
+    config: Config,
+    controllers: Controllers,
+    controller_map: ControllerMap,
+    recording_movie: bool,
+    playing_movie: Option<Arc<AtomicBool>>, // set by the player after the last recorded change
+    recorder: Option<Recorder>,
+    osd: Osd,
 }
 
 impl Emulator{
-    pub fn new(file: PathBuf) -> Emulator {
-        let starting_mode = Mode::Chip8;
+    pub fn new(file: PathBuf, options: Options, config: Config) -> Result<Emulator, String> {
+        let movie_player = options.play.as_ref().map(MoviePlayer::open).transpose()?;
+        let starting_mode = movie_player.as_ref().map_or(options.mode, |player| player.settings.mode);
 
         let mut sdl_context = sdl2::init().expect("SDL initialization failed");
         let video_subsystem = sdl_context.video().expect("SDL initialization failed");
 
-        let window = video_subsystem
-            .window("Chip8 sdl_emu", (DISPLAY_WIDTH as u32 * PIXEL_SIZE) , (DISPLAY_HEIGHT as u32 * PIXEL_SIZE))
-            .position_centered()
-            .opengl()
+        let mut window_builder = video_subsystem.window(&Self::get_title(starting_mode, Lifecycle::Idle), DISPLAY_WIDTH as u32 * options.scale, DISPLAY_HEIGHT as u32 * options.scale);
+        window_builder.position_centered().resizable().opengl();
+        if options.fullscreen {
+            window_builder.fullscreen_desktop();
+        }
+
+        let window = window_builder
             .build()
             .map_err(|e| e.to_string()).expect("Could not build window");
 
-        let canvas = window.into_canvas().build().map_err(|e| e.to_string()).expect("Could not build canvas");
+        let mut canvas_builder = window.into_canvas();
+        if options.vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder.build().map_err(|e| e.to_string()).expect("Could not build canvas");
 
         let event_pump = sdl_context.event_pump().expect("Could not get SDL event pump");
 
-        let audio_manager = AudioManager::new(&mut sdl_context, starting_mode);
+        let mut audio_manager = AudioManager::new(&mut sdl_context, starting_mode);
+        audio_manager.muted = options.mute;
+        audio_manager.volume = options.volume;
 
-        let fps = 60;
+        let fps = options.fps;
         let fps_ns = Self::get_ns_from_fps(fps);
 
-        let chip8 = Chip8::get_new_and_start(&file, starting_mode);
+        let controllers = Controllers::new(&sdl_context);
+        let controller_map = config.get_controller_map(&file);
+        let frame_composer = FrameComposer::new(options.flicker.unwrap_or(config.get_flicker_filter(&file)));
+
+        // Movies are hooked in before the machine starts, so they see it from the first tick.
+        // They keep the settings and random seed of the recording, so CXNN gives the same numbers.
+        let mut chip8 = Self::create_chip8(starting_mode, &options);
+        if let Some(path) = &options.record {
+            let settings = MovieSettings::record(&chip8);
+            chip8.set_input_hook(Some(MovieRecorder::create(path, &settings)?.into_hook()));
+        }
+        let mut playing_movie = None;
+        if let Some(player) = movie_player {
+            player.settings.apply(&mut chip8);
+            println!("Playing a movie recorded in {} at {} IPF", player.settings.mode, player.settings.ipf);
+
+            let finished = Arc::new(AtomicBool::new(false));
+            chip8.set_input_hook(Some(player.into_hook(Arc::clone(&finished))));
+            playing_movie = Some(finished);
+        }
+        let recording_movie = options.record.is_some();
+        chip8.start(&file)?;
 
-        Emulator{
+        Ok(Emulator{
             context: sdl_context,
             canvas,
             event_pump,
+            lifecycle: chip8.get_lifecycle(),
             chip8,
             current_game: file,
             fps_ns,
             fps,
-            audio_manager
+            speed: options.speed,
+            fast_forward: false,
+            audio_manager,
+            palette: options.palette,
+            screen: Screen::new(options.scaling),
+            frame_composer,
+            key_map: KeyMap::with_key_names(config.keymap.layout().clone(), KeyNames::KeyCodes),
+            config,
+            controllers,
+            controller_map,
+            options,
+            recording_movie,
+            playing_movie,
+            recorder: None,
+            osd: Osd::default(),
+        })
+    }
+
+    // Not started yet, so movies can set it up first
+    fn create_chip8(mode: Mode, options: &Options) -> Chip8{
+        let mut chip8 = Chip8::new(mode);
+        chip8.set_speed(options.speed);
+
+        for (quirk, enabled) in &options.quirks {
+            chip8.set_quirk(*quirk, *enabled);
+        }
+        if let Some(ipf) = options.ipf {
+            chip8.ipf.store(ipf, Ordering::Relaxed);
         }
+
+        chip8
     }
 
     pub fn run(&mut self){
+        let texture_creator = self.canvas.texture_creator();
+        let mut texture = self.create_screen_texture(&texture_creator);
+
+        // The machine runs at 60 Hz on its own thread, this loop only presents frames at the chosen rate
         'running: loop{
             let start = Instant::now();
 
             let events: Vec<Event> = self.event_pump.poll_iter().collect::<Vec<Event>>();
 
             for event in events {
+                if self.handle_keypad_presses(&event) || self.handle_controller_event(&event) {
+                    continue;
+                }
+
                 match event {
                     Event::Quit {..} |
                     Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
@@ -80,124 +174,292 @@ impl Emulator{
                     Event::KeyUp { keycode: Some(Keycode::Kp1), .. } => self.decrease_ipf(100),
                     Event::KeyUp { keycode: Some(Keycode::Kp6), .. } => self.increase_fps(10),
                     Event::KeyUp { keycode: Some(Keycode::Kp3), .. } => self.decrease_fps(10),
-                    Event::KeyUp { keycode: Some(Keycode::Kp5), .. } => self.restart_chip8(),
-                    Event::KeyUp { keycode: Some(Keycode::Kp7), .. } => self.change_compatibility_mode(Mode::Chip8),
-                    Event::KeyUp { keycode: Some(Keycode::Kp8), .. } => self.change_compatibility_mode(Mode::SuperChip),
-                    Event::KeyUp { keycode: Some(Keycode::Kp9), .. } => self.change_compatibility_mode(Mode::XoChip),
+                    Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => self.set_fast_forward(true),
+                    Event::KeyUp { keycode: Some(Keycode::Tab), .. } => self.set_fast_forward(false),
+                    Event::KeyUp { keycode: Some(Keycode::F1), .. } => self.change_slow_motion(),
+                    Event::KeyUp { keycode: Some(Keycode::Kp5), keymod, .. } => self.reset_chip8(Self::is_shift(keymod)),
+                    Event::KeyUp { keycode: Some(Keycode::P), .. } => self.toggle_pause(),
+                    Event::KeyUp { keycode: Some(Keycode::Kp7), keymod, .. } => self.change_compatibility_mode(Mode::Chip8, Self::is_shift(keymod)),
+                    Event::KeyUp { keycode: Some(Keycode::Kp8), keymod, .. } => self.change_compatibility_mode(Mode::SuperChip, Self::is_shift(keymod)),
+                    Event::KeyUp { keycode: Some(Keycode::Kp9), keymod, .. } => self.change_compatibility_mode(Mode::XoChip, Self::is_shift(keymod)),
                     Event::KeyUp { keycode: Some(Keycode::Kp2), .. } => self.change_game(),
-                    _ => self.handle_keypad_presses(&event),
+                    Event::KeyUp { keycode: Some(Keycode::F11), .. } => self.toggle_fullscreen(),
+                    Event::KeyUp { keycode: Some(Keycode::F10), .. } => self.change_scale_mode(),
+                    Event::KeyUp { keycode: Some(Keycode::F12), .. } => self.take_screenshot(),
+                    Event::KeyUp { keycode: Some(Keycode::F3), .. } => self.toggle_audio_recording(),
+                    Event::KeyUp { keycode: Some(Keycode::M), .. } => self.toggle_mute(),
+                    Event::KeyUp { keycode: Some(Keycode::PageUp), .. } => self.change_volume(0.1),
+                    Event::KeyUp { keycode: Some(Keycode::PageDown), .. } => self.change_volume(-0.1),
+                    Event::KeyUp { keycode: Some(Keycode::F9), keymod, .. } => {
+                        let format = if Self::is_shift(keymod) { RecordingFormat::Raw } else { RecordingFormat::Gif };
+                        self.toggle_recording(format);
+                    },
+                    Event::KeyUp { keycode: Some(Keycode::F5), .. } => self.toggle_filter("Scanlines", |filters| &mut filters.scanlines),
+                    Event::KeyUp { keycode: Some(Keycode::F6), .. } => self.toggle_filter("Pixel grid", |filters| &mut filters.pixel_grid),
+                    Event::KeyUp { keycode: Some(Keycode::F7), .. } => self.toggle_filter("Ghosting", |filters| &mut filters.ghosting),
+                    Event::KeyUp { keycode: Some(Keycode::F8), .. } => self.toggle_filter("Glow", |filters| &mut filters.glow),
+                    _ => {}
                 }
             }
 
-            self.canvas.clear();
-            self.draw_screen();
+            self.check_movie();
+            self.report_lifecycle();
+
+            let texture_size = texture.query();
+            if (texture_size.width, texture_size.height) != (self.screen.width(), self.screen.height()) {
+                texture = self.create_screen_texture(&texture_creator);
+            }
+            self.draw_screen(&mut texture);
             self.play_sounds();
 
-            let elapsed = start.elapsed().as_nanos() as u64;
-            thread::sleep(Duration::from_nanos(self.fps_ns.saturating_sub(elapsed) ));
+            // With vsync presenting already waits for the display
+            if !self.options.vsync {
+                let elapsed = start.elapsed().as_nanos() as u64;
+                thread::sleep(Duration::from_nanos(self.fps_ns.saturating_sub(elapsed) ));
+            }
+        }
+
+        // Drops a movie recorder, which flushes the file
+        self.chip8.stop();
+        self.chip8.set_input_hook(None);
+
+        if self.recorder.is_some() {
+            self.toggle_recording(RecordingFormat::Gif);
+        }
+        if self.audio_manager.is_recording() {
+            self.toggle_audio_recording();
         }
     }
 
-    fn draw_screen(&mut self){
+    fn draw_screen(&mut self, texture: &mut Texture){
+        let (tick, display) = self.chip8.get_frame();
+        self.frame_composer.push(tick, display);
+        if let Some(recorder) = self.recorder.as_mut() {
+            for (tick, display) in self.chip8.take_captured_frames() {
+                recorder.add_frame(&display, tick);
+            }
+        }
+
+        let pitch = self.screen.pitch();
+        let frame = self.screen.render(&self.frame_composer, &self.palette);
+        texture.update(None, frame, pitch).expect("Could not update screen texture");
+
+        let (output_width, output_height) = self.canvas.output_size().expect("Could not get window size");
+        let destination = self.screen.get_destination(output_width, output_height);
+
         self.canvas.set_draw_color(Color::BLACK);
         self.canvas.clear();
-        self.canvas.set_draw_color(Color::WHITE);
+        self.canvas.copy(texture, None, destination).expect("Could not draw the screen");
+        self.osd.draw(&mut self.canvas, destination);
+        self.canvas.present();
+    }
+
+    fn create_screen_texture<'a>(&self, texture_creator: &'a TextureCreator<WindowContext>) -> Texture<'a>{
+        texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, self.screen.width(), self.screen.height())
+            .expect("Could not create screen texture")
+    }
+
+    fn toggle_filter(&mut self, name: &str, filter: fn(&mut Filters) -> &mut bool){
+        let enabled = filter(&mut self.screen.filters);
+        *enabled = !*enabled;
+        println!("{} {}", name, if *enabled { "enabled" } else { "disabled" });
+    }
 
-        let display = self.get_display_copy();
+    fn toggle_fullscreen(&mut self){
+        let window = self.canvas.window_mut();
+        let fullscreen = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+
+        if let Err(e) = window.set_fullscreen(fullscreen) {
+            println!("Could not change fullscreen mode: {}", e);
+        }
+    }
+
+    fn change_scale_mode(&mut self){
+        self.screen.scale_mode = self.screen.scale_mode.next();
+        println!("Scaling changed to {:?}", self.screen.scale_mode);
+    }
 
-        for y in 0..DISPLAY_HEIGHT {
-            for x in 0..DISPLAY_WIDTH {
-                let idx = y * DISPLAY_WIDTH + x;
+    // Screenshots and recordings go to the working directory as <rom name>_<unix time>.<extension>
+    fn get_output_path(&self, extension: &str) -> PathBuf{
+        let rom_name = self.current_game.file_stem().and_then(|name| name.to_str()).unwrap_or("chip8");
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or(0);
+        PathBuf::from(format!("{}_{}.{}", rom_name, timestamp, extension))
+    }
 
-                let color = Self::get_pixel_color(&display, idx);
-                self.canvas.set_draw_color(color);
-                
-                let rect = Rect::new(
-                    (x as u32 * PIXEL_SIZE) as i32,
-                    (y as u32 * PIXEL_SIZE) as i32,
-                    PIXEL_SIZE,
-                    PIXEL_SIZE,
-                );
+    fn take_screenshot(&mut self){
+        let screenshot = self.chip8.screenshot(&self.palette, self.options.screenshot_scale);
+        let path = self.get_output_path("png");
 
-                self.canvas.fill_rect(rect).expect("Could not draw the screen");
+        match screenshot.to_png().and_then(|png| fs::write(&path, png).map_err(|e| e.to_string())) {
+            Ok(()) => println!("Screenshot saved to {}", path.display()),
+            Err(e) => println!("Could not save screenshot: {}", e),
+        }
+    }
 
+    fn toggle_recording(&mut self, format: RecordingFormat){
+        if let Some(mut recorder) = self.recorder.take() {
+            for (tick, display) in self.chip8.take_captured_frames() {
+                recorder.add_frame(&display, tick);
+            }
+            self.chip8.stop_frame_capture();
+            match recorder.finish() {
+                Ok(()) => println!("Recording stopped"),
+                Err(e) => println!("Could not finish recording: {}", e),
             }
+            return;
         }
 
-        self.canvas.present();
+        let path = match format {
+            RecordingFormat::Gif => self.get_output_path("gif"),
+            RecordingFormat::Raw => self.get_output_path("rgb"),
+        };
+
+        match Recorder::create(&path, format, self.palette, self.options.screenshot_scale) {
+            Ok(recorder) => {
+                println!("Recording to {}", path.display());
+                if format == RecordingFormat::Raw {
+                    println!("Convert with: ffmpeg -f rawvideo -pixel_format rgb24 -video_size {}x{} -framerate 60 -i {} output.mp4",
+                        recorder.width(), recorder.height(), path.display());
+                }
+                self.chip8.start_frame_capture();
+                self.recorder = Some(recorder);
+            },
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    fn toggle_mute(&mut self){
+        self.audio_manager.muted = !self.audio_manager.muted;
+        println!("Sound {}", if self.audio_manager.muted { "muted" } else { "unmuted" });
+    }
+
+    fn change_volume(&mut self, change: f32){
+        self.audio_manager.volume = (self.audio_manager.volume + change).clamp(0.0, 1.0);
+        println!("Volume changed to {:.0}%", self.audio_manager.volume * 100.0);
     }
 
-    fn get_pixel_color(display: &Display, idx: usize) -> Color{
-        if display.plane_1[idx] && display.plane_2[idx]{
-            Color::RGB(238, 238, 255)
+    fn toggle_audio_recording(&mut self){
+        if self.audio_manager.is_recording() {
+            match self.audio_manager.stop_recording() {
+                Ok(()) => println!("Audio recording stopped"),
+                Err(e) => println!("Could not finish audio recording: {}", e),
+            }
+            return;
+        }
+
+        let path = self.get_output_path("wav");
+        match self.audio_manager.start_recording(&path) {
+            Ok(()) => println!("Recording audio to {}", path.display()),
+            Err(e) => println!("Could not record audio: {}", e),
         }
-        else if display.plane_2[idx]{
-            Color::RGB(69, 101, 67)
+    }
+
+    // The keys are given back once a movie has played
+    fn check_movie(&mut self){
+        if let Some(finished) = &self.playing_movie && finished.load(Ordering::Relaxed) {
+            println!("Movie playback finished");
+            self.chip8.set_input_hook(None);
+            self.playing_movie = None;
+        }
+    }
+
+    // Movies only hold the keys, anything else that changes how the program runs would break them
+    fn is_blocked_by_movie(&self, action: &str) -> bool{
+        let blocked = self.recording_movie || self.playing_movie.is_some();
+        if blocked {
+            println!("{} is not available while a movie is recorded or played", action);
         }
-        else if display.plane_1[idx]{
-            Color::RGB(85, 68, 34)
+        blocked
+    }
+
+    fn report_lifecycle(&mut self){
+        let lifecycle = self.chip8.get_lifecycle();
+        if lifecycle == self.lifecycle {
+            return;
         }
-        else{
-            Color::RGB(135, 206, 235)
+        self.lifecycle = lifecycle;
+        self.update_title();
+
+        match lifecycle {
+            Lifecycle::Halted(HaltReason::Exit) => println!("Program exited (00FD), press Kp5 to restart"),
+            Lifecycle::Halted(HaltReason::ZeroOpcode) => println!("Program halted on 0000, press Kp5 to restart"),
+            _ => println!("Emulator {:?}", lifecycle),
         }
     }
 
     fn play_sounds(&mut self){
-        let (mode, sound_timer, sound_pattern_buffer, pitch_register) = {
-            let mode = self.chip8.compatibility_mode.lock().unwrap();
-            let state = self.chip8.state.lock().unwrap();
-            (*mode,state.sound_timer, state.sound_pattern_buffer, state.pitch_register)
+        let events = self.chip8.sound_events.lock().unwrap().take();
+        self.audio_manager.play_sounds(events, self.chip8.get_speed());
+    }
+
+    // Returns true when the key is mapped to the keypad, so it doesn't trigger hotkeys.
+    // With Ctrl held the key goes to the hotkeys instead, so the ones on keys the layout uses
+    // (e.g. the numeric keypad hotkeys with the numeric keypad layout) stay reachable.
+    fn handle_keypad_presses(&mut self, event: &Event) -> bool{
+        let (key, pressed, keymod) = match *event{
+            Event::KeyDown{keycode: Some(key), keymod, ..} => (key, true, keymod),
+            Event::KeyUp{keycode: Some(key), keymod, ..} => (key, false, keymod),
+            _ => return false,
         };
-        self.audio_manager.play_sounds(&mode, sound_timer, sound_pattern_buffer, pitch_register);
+
+        let Some(valid_key) = self.key_map.get_keypad(&Self::get_key_name(&key)) else {
+            return false;
+        };
+
+        let hotkey = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+        // Released either way, so a key let go while Ctrl is held doesn't stay pressed
+        if self.playing_movie.is_none() && !(hotkey && pressed) {
+            self.chip8.handle_input(valid_key, pressed);
+        }
+        !hotkey
     }
 
-    fn handle_keypad_presses(&mut self, event: &Event){
-        match event{
-            Event::KeyDown{keycode: Some(key), ..} => {
-                if let Some(valid_key) = Self::get_keypad_number(key){
-                    self.chip8.handle_input(valid_key, true);
-                }
+    // Returns true for controller events, mapped buttons are forwarded to the keypad
+    fn handle_controller_event(&mut self, event: &Event) -> bool{
+        let (button, pressed) = match *event {
+            Event::ControllerButtonDown { button, .. } => (button, true),
+            Event::ControllerButtonUp { button, .. } => (button, false),
+            Event::ControllerDeviceAdded { .. } | Event::ControllerDeviceRemoved { .. } => {
+                self.controllers.handle_device_event(event);
+                return true;
             }
-            Event::KeyUp{keycode: Some(key), ..} => {
-                if let Some(valid_key) = Self::get_keypad_number(key){
-                    self.chip8.handle_input(valid_key, false);
-                }
-            }
-            _ => {}
-        }
-    }
-
-    fn get_keypad_number(key: &Keycode) -> Option<KeyPad> {
-        match *key {
-            Keycode::NUM_1 => Some(KeyPad::Num1),
-            Keycode::NUM_2 => {Some(KeyPad::Num2)}
-            Keycode::NUM_3 => {Some(KeyPad::Num3)}
-            Keycode::NUM_4 => {Some(KeyPad::C)}
-            Keycode::Q => {Some(KeyPad::Num4)}
-            Keycode::W => {Some(KeyPad::Num5)}
-            Keycode::E => {Some(KeyPad::Num6)}
-            Keycode::R => {Some(KeyPad::D)}
-            Keycode::A => {Some(KeyPad::Num7)}
-            Keycode::S => {Some(KeyPad::Num8)}
-            Keycode::D => {Some(KeyPad::Num9)}
-            Keycode::F => {Some(KeyPad::E)}
-            Keycode::Z => {Some(KeyPad::A)}
-            Keycode::X => {Some(KeyPad::Num0)}
-            Keycode::C => {Some(KeyPad::B)}
-            Keycode::V => {Some(KeyPad::F)}
-            _ => {None}
-        }
-    }
-    fn get_display_copy(&self) -> Display{
-        let display = self.chip8.display.lock().unwrap();
-        *display
+            _ => return false,
+        };
+
+        if let Some(valid_key) = self.controller_map.get_keypad(button) && self.playing_movie.is_none() {
+            self.chip8.handle_input(valid_key, pressed);
+        }
+        true
+    }
+
+    fn is_shift(keymod: Mod) -> bool{
+        keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+    }
+
+    // SDL key names translated to KeyMap names, e.g. "Q" -> "q", "Keypad 7" -> "kp7"
+    fn get_key_name(key: &Keycode) -> String {
+        let name = key.name();
+        match name.strip_prefix("Keypad ") {
+            Some(keypad_key) => format!("kp{}", keypad_key.to_lowercase()),
+            None => name.to_lowercase(),
+        }
     }
     fn increase_ipf(&mut self, value: u32){
+        if self.is_blocked_by_movie("Changing IPF") {
+            return;
+        }
         let value = self.chip8.ipf.load(Ordering::Relaxed).saturating_add(value);
         self.chip8.ipf.store(value, Ordering::Relaxed);
         println!("IPF increased to {}", value);
     }
     fn decrease_ipf(&mut self, value: u32){
+        if self.is_blocked_by_movie("Changing IPF") {
+            return;
+        }
         let value = self.chip8.ipf.load(Ordering::Relaxed).saturating_sub(value);
         self.chip8.ipf.store(value, Ordering::Relaxed);
         println!("IPF decreased to {}", value);
@@ -205,36 +467,119 @@ impl Emulator{
     fn increase_fps(&mut self, additional_fps: u16){
         self.fps = self.fps.saturating_add(additional_fps);
         self.fps_ns = Self::get_ns_from_fps(self.fps);
-        self.chip8.fps_ns.store(self.fps_ns, Ordering::Relaxed);
         println!("FPS increased to {}", self.fps);
     }
     fn decrease_fps(&mut self, additional_fps: u16){
         self.fps = self.fps.saturating_sub(additional_fps);
         self.fps_ns = Self::get_ns_from_fps(self.fps);
-        self.chip8.fps_ns.store(self.fps_ns, Ordering::Relaxed);
         println!("FPS decreased to {}", self.fps);
     }
+    // Held down: runs the machine at the --fast-forward speed, the window keeps drawing at its own rate
+    fn set_fast_forward(&mut self, enabled: bool){
+        self.fast_forward = enabled;
+        self.apply_speed();
+    }
+
+    // Cycles 1x, 0.5x, 0.25x
+    fn change_slow_motion(&mut self){
+        self.speed = match self.speed {
+            speed if speed > 0.5 => 0.5,
+            speed if speed > 0.25 => 0.25,
+            _ => 1.0,
+        };
+        self.apply_speed();
+    }
+
+    fn apply_speed(&mut self){
+        let speed = if self.fast_forward { self.options.fast_forward } else { self.speed };
+        self.chip8.set_speed(speed);
+
+        if speed == UNCAPPED_SPEED {
+            println!("Speed changed to uncapped");
+        } else {
+            println!("Speed changed to {}x", speed);
+        }
+    }
+
     fn get_ns_from_fps(value: u16) -> u64{
         1_000_000_000 / value.max(1) as u64
     }
-    fn restart_chip8(&mut self){
-        let compatibility = {
-            let lock = self.chip8.compatibility_mode.lock().unwrap();
-            *lock
-        };
+    // The running game carries on when the new one can't be loaded
+    fn restart_chip8(&mut self, file: PathBuf){
+        let compatibility = self.chip8.get_compatibility_mode();
+        let mut chip8 = Self::create_chip8(compatibility, &self.options);
+        if let Err(e) = chip8.start(&file) {
+            println!("{}", e);
+            return;
+        }
         self.chip8.stop();
-        self.chip8 = Chip8::get_new_and_start(&self.current_game, compatibility);
+        self.chip8 = chip8;
+        if self.recorder.is_some() {
+            self.chip8.start_frame_capture();
+        }
+        self.controller_map = self.config.get_controller_map(&file);
+        self.frame_composer.set_filter(self.options.flicker.unwrap_or(self.config.get_flicker_filter(&file)));
+        self.current_game = file;
+        self.chip8.set_speed(if self.fast_forward { self.options.fast_forward } else { self.speed });
+        self.frame_composer.clear();
+        self.audio_manager.reset();
+    }
+    fn reset_chip8(&mut self, hard: bool){
+        if self.is_blocked_by_movie("Reset") {
+            return;
+        }
+        if hard {
+            self.chip8.hard_reset();
+            println!("Hard reset");
+        } else {
+            self.chip8.reset();
+            println!("Reset");
+        }
+        self.frame_composer.clear();
+        self.audio_manager.reset();
+    }
+    fn toggle_pause(&mut self){
+        if self.chip8.get_lifecycle() == Lifecycle::Paused {
+            self.chip8.resume();
+        } else {
+            self.chip8.pause();
+        }
     }
-    fn change_compatibility_mode(&mut self, compatibility_mode: Mode){
+    // Applies right away on a blank lores screen, with reset the program starts over in the new mode
+    fn change_compatibility_mode(&mut self, compatibility_mode: Mode, reset: bool){
+        if self.is_blocked_by_movie("Switching modes") {
+            return;
+        }
         self.chip8.set_compatibility_mode(&compatibility_mode);
-        println!("Compatibility mode changed to {:?}", compatibility_mode);
+        println!("Compatibility mode changed to {}", compatibility_mode);
+        if reset {
+            self.reset_chip8(false);
+        } else {
+            self.chip8.reset_display();
+            self.frame_composer.clear();
+        }
+        self.osd.show(&compatibility_mode.to_string());
+        self.update_title();
+    }
+    fn get_title(mode: Mode, lifecycle: Lifecycle) -> String{
+        match lifecycle {
+            Lifecycle::Paused => format!("Chip8 sdl_emu - {} - Paused", mode),
+            Lifecycle::Halted(_) => format!("Chip8 sdl_emu - {} - Halted", mode),
+            _ => format!("Chip8 sdl_emu - {}", mode),
+        }
+    }
+    fn update_title(&mut self){
+        let title = Self::get_title(self.chip8.get_compatibility_mode(), self.chip8.get_lifecycle());
+        if let Err(e) = self.canvas.window_mut().set_title(&title) {
+            println!("Could not set window title: {}", e);
+        }
     }
     fn change_game(&mut self){
-        let file = file_picker::pick_file();
-        if let Some(file) = file {
-            self.current_game = file;
+        if self.is_blocked_by_movie("Changing the game") {
+            return;
         }
-        self.restart_chip8();
+        let file = file_picker::pick_file().unwrap_or(self.current_game.clone());
+        self.restart_chip8(file);
     }
 
 
@@ -1,16 +1,33 @@
 mod emulator;
 mod sound;
 mod file_picker;
+mod cli;
+mod movie;
+mod recording;
+mod config;
+mod controller;
+mod video;
 
-use rfd::FileDialog;
+use crate::cli::{Options, USAGE};
+use crate::config::Config;
 use crate::emulator::Emulator;
-use crate::file_picker::pick_file;
 
 pub fn main() -> Result<(), String> {
-    let file = file_picker::pick_file();
+    let options = Options::parse(std::env::args().skip(1))?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let config = Config::load(options.config.as_ref())?;
+
+    let file = match &options.rom {
+        Some(rom) => Some(rom.clone()),
+        None => file_picker::pick_file(),
+    };
 
     if let Some(file) = file {
-        let mut emulator = Emulator::new(file);
+        let mut emulator = Emulator::new(file, options, config)?;
         emulator.run();
     }
 
@@ -0,0 +1,172 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use chip8_lib::chip_8::{Chip8, InputHook, Mode};
+use chip8_lib::cpu_state::Quirk;
+
+// Movie file format: a header line, the settings the program ran with ("mode <mode>", "ipf <N>",
+// "seed <N>" and one "quirk <name>=<on|off>" line per quirk), then "<tick> <keypad mask in hex>" lines
+// written every time the pressed keypad keys change. Ticks are the machine's 60 Hz ticks,
+// so a movie replays the same at any speed or frame rate.
+const MOVIE_HEADER: &str = "chip8-movie 3";
+
+// Everything besides the keys that decides how a program runs, CXNN included
+#[derive(PartialEq, Clone, Debug)]
+pub struct MovieSettings{
+    pub mode: Mode,
+    pub ipf: u32,
+    pub seed: u64,
+    pub quirks: Vec<(Quirk, bool)>,
+}
+
+impl MovieSettings{
+    // The settings of a machine that hasn't started yet, its random numbers get a new seed
+    pub fn record(chip8: &Chip8) -> MovieSettings{
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(0);
+        chip8.state.lock().unwrap().seed_rng(seed);
+
+        MovieSettings{
+            mode: chip8.get_compatibility_mode(),
+            ipf: chip8.ipf.load(Ordering::Relaxed),
+            seed,
+            quirks: Quirk::ALL.iter().map(|&quirk| (quirk, chip8.get_quirk(quirk))).collect(),
+        }
+    }
+
+    // Overrides the settings given on the command line, must be called before the machine starts
+    pub fn apply(&self, chip8: &mut Chip8){
+        chip8.set_compatibility_mode(&self.mode);
+        chip8.ipf.store(self.ipf, Ordering::Relaxed);
+        for &(quirk, enabled) in &self.quirks {
+            chip8.set_quirk(quirk, enabled);
+        }
+        chip8.state.lock().unwrap().seed_rng(self.seed);
+    }
+
+    fn write(&self, writer: &mut impl Write) -> std::io::Result<()>{
+        writeln!(writer, "mode {}", self.mode)?;
+        writeln!(writer, "ipf {}", self.ipf)?;
+        writeln!(writer, "seed {}", self.seed)?;
+        for &(quirk, enabled) in &self.quirks {
+            writeln!(writer, "quirk {}={}", quirk, if enabled { "on" } else { "off" })?;
+        }
+        Ok(())
+    }
+}
+
+pub struct MovieRecorder{
+    writer: BufWriter<File>,
+    last_keys: Option<u16>,
+}
+
+impl MovieRecorder{
+    pub fn create(path: &PathBuf, settings: &MovieSettings) -> Result<MovieRecorder, String>{
+        let file = File::create(path).map_err(|e| format!("Could not create movie file: {}", e))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", MOVIE_HEADER).map_err(|e| e.to_string())?;
+        settings.write(&mut writer).map_err(|e| e.to_string())?;
+
+        Ok(MovieRecorder{
+            writer,
+            last_keys: None,
+        })
+    }
+
+    pub fn record(&mut self, tick: u64, keys: u16){
+        if self.last_keys == Some(keys) {
+            return;
+        }
+        self.last_keys = Some(keys);
+
+        if let Err(e) = writeln!(self.writer, "{} {:04X}", tick, keys) {
+            println!("Could not write movie file: {}", e);
+        }
+    }
+
+    // Records the keys of every tick on the execution thread, the file is flushed when the hook is dropped
+    pub fn into_hook(mut self) -> InputHook{
+        Box::new(move |tick, keys| {
+            self.record(tick, keys);
+            keys
+        })
+    }
+}
+
+pub struct MoviePlayer{
+    pub settings: MovieSettings,
+    inputs: Vec<(u64, u16)>,
+    position: usize,
+}
+
+impl MoviePlayer{
+    pub fn open(path: &PathBuf) -> Result<MoviePlayer, String>{
+        let content = fs::read_to_string(path).map_err(|e| format!("Could not read movie file: {}", e))?;
+        let mut lines = content.lines();
+
+        if lines.next() != Some(MOVIE_HEADER) {
+            return Err("Not a movie file".to_string());
+        }
+
+        let (mut mode, mut ipf, mut seed, mut quirks) = (None, None, None, vec![]);
+        let mut inputs = vec![];
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let invalid = || format!("Invalid movie line: {}", line);
+            let (name, value) = line.split_once(' ').ok_or_else(invalid)?;
+            let value = value.trim();
+
+            match name {
+                "mode" => mode = Some(value.parse()?),
+                "ipf" => ipf = Some(value.parse().map_err(|_| invalid())?),
+                "seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+                "quirk" => quirks.push(Quirk::parse_setting(value)?),
+                _ => {
+                    let tick = name.parse().map_err(|_| invalid())?;
+                    let keys = u16::from_str_radix(value, 16).map_err(|_| invalid())?;
+                    inputs.push((tick, keys));
+                }
+            }
+        }
+
+        let (Some(mode), Some(ipf), Some(seed)) = (mode, ipf, seed) else {
+            return Err("The movie file has no mode, ipf or seed".to_string());
+        };
+
+        Ok(MoviePlayer{
+            settings: MovieSettings{ mode, ipf, seed, quirks },
+            inputs,
+            position: 0,
+        })
+    }
+
+    // Returns the keypad mask recorded for this tick, if the keys changed on it
+    pub fn keys_for_tick(&mut self, tick: u64) -> Option<u16>{
+        let mut keys = None;
+        while let Some(&(input_tick, input_keys)) = self.inputs.get(self.position) {
+            if input_tick > tick {
+                break;
+            }
+            keys = Some(input_keys);
+            self.position += 1;
+        }
+        keys
+    }
+
+    pub fn is_finished(&self) -> bool{
+        self.position >= self.inputs.len()
+    }
+
+    // Replaces the keys of every tick with the recorded ones, finished is set after the last change
+    pub fn into_hook(mut self, finished: Arc<AtomicBool>) -> InputHook{
+        Box::new(move |tick, keys| {
+            let keys = self.keys_for_tick(tick).unwrap_or(keys);
+            if self.is_finished() {
+                finished.store(true, Ordering::Relaxed);
+            }
+            keys
+        })
+    }
+}
@@ -0,0 +1,173 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use gif::{Encoder, Frame, Repeat};
+use chip8_lib::display::Display;
+use chip8_lib::palette::Palette;
+use chip8_lib::parameters::*;
+use chip8_lib::sound::TICK_NS;
+
+const NS_PER_CENTISECOND: u64 = 10_000_000;
+// Browsers play shorter GIF frame delays at 100ms, frames are merged until they last this long
+const MIN_GIF_DELAY: u64 = 2;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum RecordingFormat{
+    Gif,
+    Raw, // headerless RGB24 frames for ffmpeg
+}
+
+enum Output{
+    Gif(Encoder<BufWriter<File>>),
+    Raw(BufWriter<File>),
+}
+
+// Records the display of every emulated 60 Hz tick, as captured by the execution thread, whatever the
+// presentation rate. Pixels are palette indexes:
+// 0 background, 1 plane 1, 2 plane 2, 3 both planes.
+pub struct Recorder{
+    output: Output,
+    palette: Palette,
+    scale: usize,
+    pending_frame: Option<Vec<u8>>,
+    pending_ns: u64,
+    last_tick: Option<u64>,
+}
+
+impl Recorder{
+    pub fn create(path: &PathBuf, format: RecordingFormat, palette: Palette, scale: u32) -> Result<Recorder, String>{
+        let scale = scale.max(1) as usize;
+        let file = File::create(path).map_err(|e| format!("Could not create recording file: {}", e))?;
+        let writer = BufWriter::new(file);
+
+        let output = match format {
+            RecordingFormat::Gif => {
+                let colors: Vec<u8> = [palette.background, palette.plane_1, palette.plane_2, palette.mixed]
+                    .iter()
+                    .flat_map(|&(r, g, b)| [r, g, b])
+                    .collect();
+
+                let mut encoder = Encoder::new(writer, (DISPLAY_WIDTH * scale) as u16, (DISPLAY_HEIGHT * scale) as u16, &colors)
+                    .map_err(|e| format!("Could not create GIF: {}", e))?;
+                encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+                Output::Gif(encoder)
+            },
+            RecordingFormat::Raw => Output::Raw(writer),
+        };
+
+        Ok(Recorder{
+            output,
+            palette,
+            scale,
+            pending_frame: None,
+            pending_ns: 0,
+            last_tick: None,
+        })
+    }
+
+    pub fn width(&self) -> usize{
+        DISPLAY_WIDTH * self.scale
+    }
+
+    pub fn height(&self) -> usize{
+        DISPLAY_HEIGHT * self.scale
+    }
+
+    // Takes the display after the given tick. Missing ticks repeat the next frame, a tick that was
+    // already recorded is skipped. Raw frames are written once per tick for playback at 60 fps,
+    // GIF frames are held until the display changes so that each one gets its real duration.
+    pub fn add_frame(&mut self, display: &Display, tick: u64){
+        let ticks = match self.last_tick {
+            Some(last_tick) if tick == last_tick => return,
+            Some(last_tick) if tick > last_tick => tick - last_tick,
+            _ => 1, // the first frame, or a new machine counting from 0 again
+        };
+        self.last_tick = Some(tick);
+        let frame = self.get_indexed_frame(display);
+
+        let result = match &mut self.output {
+            Output::Raw(writer) => {
+                let rgb: Vec<u8> = frame.iter().flat_map(|&index| {
+                    let (r, g, b) = Self::get_color(&self.palette, index);
+                    [r, g, b]
+                }).collect();
+                (0..ticks).try_for_each(|_| writer.write_all(&rgb)).map_err(|e| e.to_string())
+            },
+            Output::Gif(_) => {
+                let changed = self.pending_frame.as_ref() != Some(&frame);
+                // A frame shown too briefly is replaced, its time goes to the next one
+                let result = if changed && self.pending_ns >= MIN_GIF_DELAY * NS_PER_CENTISECOND {
+                    self.write_pending_frame()
+                } else {
+                    Ok(())
+                };
+                if changed {
+                    self.pending_frame = Some(frame);
+                }
+                self.pending_ns += ticks * TICK_NS;
+                result
+            },
+        };
+
+        if let Err(e) = result {
+            println!("Could not write recording: {}", e);
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), String>{
+        if let Output::Gif(_) = self.output {
+            self.write_pending_frame()?;
+        }
+
+        match self.output {
+            Output::Gif(encoder) => encoder.into_inner().map_err(|e| e.to_string())?.flush().map_err(|e| e.to_string()),
+            Output::Raw(mut writer) => writer.flush().map_err(|e| e.to_string()),
+        }
+    }
+
+    // Leftover time below a centisecond is carried over to the next frame
+    fn write_pending_frame(&mut self) -> Result<(), String>{
+        let Some(pixels) = self.pending_frame.take() else {
+            return Ok(());
+        };
+        let Output::Gif(encoder) = &mut self.output else {
+            return Ok(());
+        };
+
+        let delay = self.pending_ns / NS_PER_CENTISECOND;
+        self.pending_ns %= NS_PER_CENTISECOND;
+
+        let frame = Frame{
+            width: (DISPLAY_WIDTH * self.scale) as u16,
+            height: (DISPLAY_HEIGHT * self.scale) as u16,
+            delay: delay.clamp(MIN_GIF_DELAY, u16::MAX as u64) as u16,
+            buffer: Cow::Owned(pixels),
+            ..Frame::default()
+        };
+
+        encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+
+    fn get_indexed_frame(&self, display: &Display) -> Vec<u8>{
+        let (width, height) = (self.width(), self.height());
+        let mut pixels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y / self.scale) * DISPLAY_WIDTH + x / self.scale;
+                pixels.push(display.plane_1[idx] as u8 | (display.plane_2[idx] as u8) << 1);
+            }
+        }
+        pixels
+    }
+
+    fn get_color(palette: &Palette, index: u8) -> (u8, u8, u8){
+        match index {
+            1 => palette.plane_1,
+            2 => palette.plane_2,
+            3 => palette.mixed,
+            _ => palette.background,
+        }
+    }
+}
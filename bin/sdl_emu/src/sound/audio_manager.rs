@@ -1,14 +1,41 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use sdl2::audio::{AudioDevice, AudioSpecDesired};
 use sdl2::{AudioSubsystem, Sdl};
-use chip8_lib::chip_8::Mode;
-use crate::sound::pattern_wave::PatternWave;
-use crate::sound::square_wave::SquareWave;
+use chip8_lib::chip_8::{Mode, UNCAPPED_SPEED};
+use chip8_lib::wav::WavWriter;
+use chip8_lib::sound::{SoundEvent, SoundGenerator, DEFAULT_VOLUME};
+use crate::sound::sound_output::SoundOutput;
 
+// Samples played by the audio callback while a recording is running, None otherwise
+pub type AudioCapture = Arc<Mutex<Option<Vec<f32>>>>;
+
+// Never waits in the audio callback: while the main thread holds the lock the samples are kept
+// in pending and handed over on a later call
+pub fn capture_samples(capture: &AudioCapture, pending: &mut Vec<f32>, samples: &[f32]){
+    let Ok(mut capture) = capture.try_lock() else {
+        pending.extend_from_slice(samples);
+        return;
+    };
+
+    match capture.as_mut() {
+        Some(buffer) => {
+            buffer.append(pending);
+            buffer.extend_from_slice(samples);
+        },
+        None => pending.clear(),
+    }
+}
+
+// The device keeps running and plays silence while the sound timer is 0, so a recording has no gaps
 pub struct AudioManager{
-    pub pattern_wave: Option<AudioDevice<PatternWave>>,
-    pub square_wave: Option<AudioDevice<SquareWave>>,
-    mode: Mode,
-    playing_sounds: bool,
+    pub device: Option<AudioDevice<SoundOutput>>,
+    pub muted: bool,
+    pub volume: f32,
+    capture: AudioCapture,
+    wav_writer: Option<WavWriter<BufWriter<File>>>,
 }
 
 impl AudioManager{
@@ -18,97 +45,82 @@ impl AudioManager{
             channels: Some(1),
             samples: None,
         };
+        let capture: AudioCapture = Arc::new(Mutex::new(None));
+
+        let device = Self::get_device(&desired_spec, &context, &capture, mode);
+        if let Some(device) = &device {
+            device.resume();
+        }
 
         AudioManager{
-            pattern_wave: Self::get_pattern_wave(&desired_spec, &context),
-            square_wave: Self::get_square_wave(&desired_spec, &context),
-            mode,
-            playing_sounds: false,
+            device,
+            muted: false,
+            volume: DEFAULT_VOLUME,
+            capture,
+            wav_writer: None,
         }
     }
-    fn get_pattern_wave(spec: &AudioSpecDesired, context: &Sdl) -> Option<AudioDevice<PatternWave>>{
+    fn get_device(spec: &AudioSpecDesired, context: &Sdl, capture: &AudioCapture, mode: Mode) -> Option<AudioDevice<SoundOutput>>{
         let audio_subsystem = context.audio().ok()?;
-        let pattern_device = audio_subsystem.open_playback(None, &spec, |spec| {
-            PatternWave {
-                phase: 0.0,
-                phase_inc: 0.0,
-                volume: 0.05,
-                pattern: [0; 16],
-                pitch_register: 64,
-            }
+        let device = audio_subsystem.open_playback(None, &spec, |spec| {
+            SoundOutput::new(SoundGenerator::new(spec.freq as u32, mode), Arc::clone(capture))
         }).ok()?;
-        Some(pattern_device)
-    }
-    fn get_square_wave(spec: &AudioSpecDesired, context: &Sdl) -> Option<AudioDevice<SquareWave>>{
-        let audio_subsystem = context.audio().ok()?;
-        let square_device = audio_subsystem.open_playback(None, &spec, |spec| {
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.05
-            }
-        }).ok()?;
-        Some(square_device)
+        Some(device)
     }
 
-    fn stop_sounds(&mut self){
-        if let Some(pattern_device) = &self.pattern_wave{
-            pattern_device.pause();
-        }
-        if let Some(square_device) = &self.square_wave{
-            square_device.pause();
+    // Uncapped speed has no fixed timing to play the events at, so it stays silent
+    pub fn play_sounds(&mut self, events: Vec<SoundEvent>, speed: f32){
+        if let Some(device) = self.device.as_mut() {
+            let mut output = device.lock();
+            output.player.generator.muted = self.muted || speed == UNCAPPED_SPEED;
+            output.player.generator.volume = self.volume;
+            output.player.set_speed(if speed == UNCAPPED_SPEED { 1.0 } else { speed });
+            output.player.queue(events);
         }
+
+        self.update_recording();
     }
 
-    pub fn play_sounds(&mut self, mode: &Mode, sound_timer: u8, sound_pattern_buffer: [u8; 16], pitch_register: u8){
-        if *mode != self.mode {
-           self.stop_sounds();
-            self.mode = *mode;
+    pub fn reset(&mut self){
+        if let Some(device) = self.device.as_mut() {
+            device.lock().player.reset();
         }
+    }
 
-        match self.mode {
-            Mode::XoChip => self.play_pattern_wave(sound_timer, sound_pattern_buffer, pitch_register),
-            _ => self.play_square_wave(sound_timer),
-        }
+    pub fn is_recording(&self) -> bool{
+        self.wav_writer.is_some()
     }
 
-    fn play_square_wave(&mut self, sound_timer: u8){
-        if let Some(device) = &self.square_wave{
-
-            if !self.playing_sounds && sound_timer > 0{
-                device.resume();
-                self.playing_sounds = true;
-            }
-            else if sound_timer == 0 && self.playing_sounds{
-                device.pause();
-                self.playing_sounds = false;
-            }
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), String>{
+        let sample_rate = self.device.as_ref().map(|device| device.spec().freq as u32).ok_or("No audio device")?;
+        self.wav_writer = Some(WavWriter::create(path, sample_rate)?);
+        *self.capture.lock().unwrap() = Some(vec![]);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<(), String>{
+        self.update_recording();
+        *self.capture.lock().unwrap() = None;
+
+        match self.wav_writer.take() {
+            Some(wav_writer) => wav_writer.finish().map(|_| ()),
+            None => Ok(()),
         }
     }
 
-    fn play_pattern_wave(&mut self, sound_timer: u8, sound_pattern_buffer: [u8; 16], pitch_register: u8){
-        if let Some(device) = self.pattern_wave.as_mut() {
-
-            if sound_timer > 0 {
-                {
-                    let mut lock = device.lock();
-                    lock.pattern.copy_from_slice(&sound_pattern_buffer);
-                    lock.pitch_register = pitch_register;
-                    lock.update_pitch(44_100.0);
-
-                    if !self.playing_sounds {
-                        lock.phase = 0.0;
-                    }
-                }
-            }
-
-            if sound_timer > 0 && !self.playing_sounds {
-                device.resume();
-                self.playing_sounds = true;
-            } else if sound_timer == 0 && self.playing_sounds {
-                device.pause();
-                self.playing_sounds = false;
-            }
+    // Moves the captured samples into the WAV file, called every frame
+    fn update_recording(&mut self){
+        let Some(wav_writer) = self.wav_writer.as_mut() else {
+            return;
+        };
+
+        let samples = match self.capture.lock().unwrap().as_mut() {
+            Some(buffer) => std::mem::take(buffer),
+            None => return,
+        };
+
+        if let Err(e) = wav_writer.write_samples(&samples) {
+            println!("Could not write WAV file: {}", e);
         }
     }
-}
\ No newline at end of file
+}
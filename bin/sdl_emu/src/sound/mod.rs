@@ -1,3 +1,2 @@
 pub mod audio_manager;
-mod pattern_wave;
-mod square_wave;
\ No newline at end of file
+mod sound_output;
\ No newline at end of file
@@ -0,0 +1,29 @@
+use sdl2::audio::AudioCallback;
+use chip8_lib::sound::{SoundGenerator, SoundPlayer};
+use crate::sound::audio_manager::{capture_samples, AudioCapture};
+
+// Plays the machine's sound events on the SDL audio device and hands the samples to a running recording
+pub struct SoundOutput {
+    pub player: SoundPlayer,
+    pub capture: AudioCapture,
+    pending_capture: Vec<f32>,
+}
+
+impl SoundOutput {
+    pub fn new(generator: SoundGenerator, capture: AudioCapture) -> SoundOutput {
+        SoundOutput {
+            player: SoundPlayer::new(generator),
+            capture,
+            pending_capture: vec![],
+        }
+    }
+}
+
+impl AudioCallback for SoundOutput {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.player.fill(out);
+        capture_samples(&self.capture, &mut self.pending_capture, out);
+    }
+}
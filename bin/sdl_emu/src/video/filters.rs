@@ -0,0 +1,128 @@
+// CPU side post-processing of RGB frames stored as f32 channels (0.0 - 255.0).
+// Scanlines, pixel grid and glow need room between display pixels, so those
+// work on the frame upscaled by FILTER_SCALE.
+
+pub const FILTER_SCALE: usize = 4;
+const CHANNELS: usize = 3;
+const GHOSTING_PERSISTENCE: f32 = 0.55; // share of the previous frame kept every frame
+const SCANLINE_BRIGHTNESS: f32 = 0.55;
+const GRID_BRIGHTNESS: f32 = 0.7;
+const GLOW_STRENGTH: f32 = 0.6;
+const GLOW_RADIUS: usize = 3;
+
+#[derive(Default)]
+pub struct Filters{
+    pub scanlines: bool,
+    pub pixel_grid: bool,
+    pub ghosting: bool,
+    pub glow: bool,
+    history: Vec<f32>,
+    blur: Vec<f32>,
+    blur_pass: Vec<f32>,
+}
+
+impl Filters{
+    pub fn needs_upscale(&self) -> bool{
+        self.scanlines || self.pixel_grid || self.glow
+    }
+
+    // Phosphor persistence: every pixel fades towards its new color instead of switching at once,
+    // so sprites erased and redrawn between frames (XOR flicker) stay visible
+    pub fn apply_ghosting(&mut self, frame: &mut [f32]){
+        if !self.ghosting {
+            self.history.clear();
+            return;
+        }
+        if self.history.len() != frame.len() {
+            self.history = frame.to_vec();
+        }
+
+        for (channel, previous) in frame.iter_mut().zip(self.history.iter_mut()) {
+            *previous = *channel + (*previous - *channel) * GHOSTING_PERSISTENCE;
+            *channel = *previous;
+        }
+    }
+
+    pub fn apply_upscaled(&mut self, frame: &mut [f32], width: usize, height: usize){
+        if self.pixel_grid {
+            apply_pixel_grid(frame, width, height);
+        }
+        if self.scanlines {
+            apply_scanlines(frame, width, height);
+        }
+        if self.glow {
+            self.apply_glow(frame, width, height);
+        }
+    }
+
+    // Light bleeds from bright pixels into darker neighbours, flat areas stay untouched
+    fn apply_glow(&mut self, frame: &mut [f32], width: usize, height: usize){
+        self.blur.resize(frame.len(), 0.0);
+        self.blur_pass.resize(frame.len(), 0.0);
+        box_blur(frame, &mut self.blur_pass, &mut self.blur, width, height);
+
+        for (channel, blurred) in frame.iter_mut().zip(self.blur.iter()) {
+            *channel = (*channel + (blurred - *channel).max(0.0) * GLOW_STRENGTH).min(255.0);
+        }
+    }
+}
+
+pub fn upscale(source: &[f32], width: usize, height: usize, scale: usize, destination: &mut Vec<f32>){
+    let scaled_width = width * scale;
+    destination.resize(scaled_width * height * scale * CHANNELS, 0.0);
+
+    for y in 0..height * scale {
+        for x in 0..scaled_width {
+            let from = ((y / scale) * width + x / scale) * CHANNELS;
+            let to = (y * scaled_width + x) * CHANNELS;
+            destination[to..to + CHANNELS].copy_from_slice(&source[from..from + CHANNELS]);
+        }
+    }
+}
+
+// Darkens the last row of every display pixel
+fn apply_scanlines(frame: &mut [f32], width: usize, height: usize){
+    for y in (FILTER_SCALE - 1..height).step_by(FILTER_SCALE) {
+        let row = &mut frame[y * width * CHANNELS..(y + 1) * width * CHANNELS];
+        row.iter_mut().for_each(|channel| *channel *= SCANLINE_BRIGHTNESS);
+    }
+}
+
+// Darkens the right and bottom edge of every display pixel, like the gaps between LCD cells
+fn apply_pixel_grid(frame: &mut [f32], width: usize, height: usize){
+    for y in 0..height {
+        for x in 0..width {
+            if x % FILTER_SCALE == FILTER_SCALE - 1 || y % FILTER_SCALE == FILTER_SCALE - 1 {
+                let idx = (y * width + x) * CHANNELS;
+                frame[idx..idx + CHANNELS].iter_mut().for_each(|channel| *channel *= GRID_BRIGHTNESS);
+            }
+        }
+    }
+}
+
+// Separable box blur, horizontal pass into `horizontal`, vertical pass into `output`
+fn box_blur(frame: &[f32], horizontal: &mut [f32], output: &mut [f32], width: usize, height: usize){
+    let window = (GLOW_RADIUS * 2 + 1) as f32;
+
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..CHANNELS {
+                let from = x.saturating_sub(GLOW_RADIUS);
+                let to = (x + GLOW_RADIUS).min(width - 1);
+                let sum: f32 = (from..=to).map(|sx| frame[(y * width + sx) * CHANNELS + c]).sum();
+                horizontal[(y * width + x) * CHANNELS + c] = sum / window;
+            }
+        }
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..CHANNELS {
+                let from = y.saturating_sub(GLOW_RADIUS);
+                let to = (y + GLOW_RADIUS).min(height - 1);
+                let sum: f32 = (from..=to).map(|sy| horizontal[(sy * width + x) * CHANNELS + c]).sum();
+                output[(y * width + x) * CHANNELS + c] = sum / window;
+            }
+        }
+    }
+}
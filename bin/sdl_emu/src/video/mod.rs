@@ -0,0 +1,3 @@
+pub mod screen;
+pub mod filters;
+pub mod osd;
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+
+const SHOW_TIME: Duration = Duration::from_secs(2);
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+// Window pixels per font pixel for each 128 display pixels of width
+const SCALE_DIVISOR: u32 = 128;
+
+// Short messages drawn over the top left of the screen for a moment, so they are seen in fullscreen too
+#[derive(Default)]
+pub struct Osd{
+    text: String,
+    until: Option<Instant>,
+}
+
+impl Osd{
+    pub fn show(&mut self, text: &str){
+        self.text = text.to_uppercase();
+        self.until = Some(Instant::now() + SHOW_TIME);
+    }
+
+    pub fn draw(&self, canvas: &mut WindowCanvas, screen: Rect){
+        if self.until.is_none_or(|until| Instant::now() >= until) {
+            return;
+        }
+
+        let scale = (screen.width() / SCALE_DIVISOR).max(1) as i32;
+        let (x, y) = (screen.x() + 2 * scale, screen.y() + 2 * scale);
+        let width = self.text.chars().count() as i32 * (GLYPH_WIDTH + 1) + 1;
+
+        let mut pixels = vec![];
+        for (idx, character) in self.text.chars().enumerate() {
+            let left = x + (idx as i32 * (GLYPH_WIDTH + 1) + 1) * scale;
+            for (row, bits) in Self::get_glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> column) != 0 {
+                        pixels.push(Rect::new(left + column * scale, y + (row as i32 + 1) * scale, scale as u32, scale as u32));
+                    }
+                }
+            }
+        }
+
+        let background = Rect::new(x, y, (width * scale) as u32, ((GLYPH_HEIGHT + 2) * scale) as u32);
+        canvas.set_draw_color(Color::BLACK);
+        let result = canvas.fill_rect(background).and_then(|_| {
+            canvas.set_draw_color(Color::WHITE);
+            canvas.fill_rects(&pixels)
+        });
+        if let Err(e) = result {
+            println!("Could not draw the on-screen display: {}", e);
+        }
+    }
+
+    // 3x5 glyphs for the mode names, anything else is drawn as a space
+    fn get_glyph(character: char) -> [u8; 5]{
+        match character {
+            'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+            'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+            'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+            'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+            'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+            'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+            'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+            'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+            'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+            'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+            'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+            'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+            'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+            'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+            'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+            '8' => [0b010, 0b101, 0b010, 0b101, 0b010],
+            '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+            _ => [0; 5],
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::str::FromStr;
+use sdl2::rect::Rect;
+use chip8_lib::frame_composer::FrameComposer;
+use chip8_lib::palette::Palette;
+use chip8_lib::parameters::*;
+use crate::video::filters::{upscale, Filters, FILTER_SCALE};
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum ScaleMode{
+    Integer, // whole multiples of the display size only, sharpest pixels
+    Fit,     // as large as the window allows while keeping the 2:1 aspect ratio
+}
+
+impl ScaleMode{
+    pub fn next(&self) -> ScaleMode{
+        match self {
+            ScaleMode::Integer => ScaleMode::Fit,
+            ScaleMode::Fit => ScaleMode::Integer,
+        }
+    }
+}
+
+impl FromStr for ScaleMode{
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ScaleMode, String>{
+        match name.to_lowercase().as_str() {
+            "integer" => Ok(ScaleMode::Integer),
+            "fit" => Ok(ScaleMode::Fit),
+            _ => Err(format!("Unknown scaling mode: {}", name))
+        }
+    }
+}
+
+pub const BYTES_PER_PIXEL: usize = 3;
+
+// Turns the chip8 display into an RGB24 frame for a streaming texture, runs the filters
+// and works out where it goes inside the window
+pub struct Screen{
+    pub scale_mode: ScaleMode,
+    pub filters: Filters,
+    native: Vec<f32>,
+    upscaled: Vec<f32>,
+    frame: Vec<u8>,
+}
+
+impl Screen{
+    pub fn new(scale_mode: ScaleMode) -> Screen{
+        Screen{
+            scale_mode,
+            filters: Filters::default(),
+            native: vec![0.0; DISPLAY_SIZE * BYTES_PER_PIXEL],
+            upscaled: vec![],
+            frame: vec![],
+        }
+    }
+
+    fn scale(&self) -> usize{
+        if self.filters.needs_upscale() { FILTER_SCALE } else { 1 }
+    }
+
+    // Size of the rendered frame, changes when upscaling filters are switched on or off
+    pub fn width(&self) -> u32{
+        (DISPLAY_WIDTH * self.scale()) as u32
+    }
+
+    pub fn height(&self) -> u32{
+        (DISPLAY_HEIGHT * self.scale()) as u32
+    }
+
+    pub fn pitch(&self) -> usize{
+        self.width() as usize * BYTES_PER_PIXEL
+    }
+
+    pub fn render(&mut self, frames: &FrameComposer, palette: &Palette) -> &[u8]{
+        for (idx, pixel) in self.native.chunks_exact_mut(BYTES_PER_PIXEL).enumerate() {
+            let (r, g, b) = frames.get_pixel_color(palette, idx);
+            pixel.copy_from_slice(&[r as f32, g as f32, b as f32]);
+        }
+        self.filters.apply_ghosting(&mut self.native);
+
+        let scale = self.scale();
+        let output = if scale > 1 {
+            upscale(&self.native, DISPLAY_WIDTH, DISPLAY_HEIGHT, scale, &mut self.upscaled);
+            self.filters.apply_upscaled(&mut self.upscaled, DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+            &self.upscaled
+        } else {
+            &self.native
+        };
+
+        self.frame.clear();
+        self.frame.extend(output.iter().map(|channel| channel.round().clamp(0.0, 255.0) as u8));
+        &self.frame
+    }
+
+    // Centered destination rectangle, the remaining area is letterboxed
+    pub fn get_destination(&self, output_width: u32, output_height: u32) -> Rect{
+        let (width, height) = match self.scale_mode {
+            ScaleMode::Integer => {
+                let scale = (output_width / DISPLAY_WIDTH as u32).min(output_height / DISPLAY_HEIGHT as u32).max(1);
+                (DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale)
+            }
+            ScaleMode::Fit => {
+                let width = output_width.min(output_height * (DISPLAY_WIDTH / DISPLAY_HEIGHT) as u32).max(1);
+                (width, (width * DISPLAY_HEIGHT as u32 / DISPLAY_WIDTH as u32).max(1))
+            }
+        };
+
+        let x = (output_width as i32 - width as i32) / 2;
+        let y = (output_height as i32 - height as i32) / 2;
+        Rect::new(x, y, width, height)
+    }
+}
@@ -0,0 +1,104 @@
+// Throughput of the core loop, reported in emulated instructions per second.
+//
+// Budget: XO-Chip ROMs run 1000 instructions per 60 Hz frame, so a whole frame of the
+// sprite-heavy mix (tick_xochip_1000_ipf) should stay under 1 ms, 6% of the 16.7 ms it represents.
+// The frame time is reported against it, timings depend too much on the host to fail the run.
+//
+// Save a baseline before optimizing and compare against it afterwards, criterion reports regressions:
+//   cargo bench -p chip8_lib --features test-support -- --save-baseline before
+//   cargo bench -p chip8_lib --features test-support -- --baseline before
+
+use std::hint::black_box;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::machine::Machine;
+use chip8_lib::parameters::*;
+
+const INSTRUCTIONS: u64 = 1000;
+const FRAME_BUDGET: Duration = Duration::from_millis(1);
+const BUDGET_FRAMES: u32 = 600;
+
+// Register arithmetic, skips and a jump back to the start
+const ARITHMETIC: &[u16] = &[0x6001, 0x6102, 0x8014, 0x8105, 0x8216, 0x830E, 0x3000, 0x7101, 0x9010, 0xF01E, 0x1200];
+// 16x16 sprites, drawn at 2x in lores
+const SPRITES_16: &[u16] = &[0xA300, 0x6000, 0x6100, 0xD010, 0x7004, 0x7102, 0x1206];
+// 8x15 sprites
+const SPRITES_8: &[u16] = &[0xA300, 0x6000, 0x6100, 0xD01F, 0x7003, 0x7101, 0x1206];
+const SCROLLS: &[u16] = &[0x00C1, 0x00FB, 0x00B1, 0x00FC, 0x1200];
+// What XO-Chip games do in a frame: sprites on two planes, arithmetic, memory and timers
+const MIXED: &[u16] = &[
+    0xA300, 0xF301, 0x6000, 0x6100,
+    0xD018, 0x7005, 0x8014, 0x8206, 0xF265, 0xF015, 0xF107, 0x1200,
+];
+
+// The program at 0x200 and sprite data at 0x300
+fn rom(program: &[u16]) -> Vec<u8>{
+    let mut rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    rom.resize(0x100, 0);
+    rom.extend((0x300..0x340).map(|address| address as u8 ^ 0x5A));
+    rom
+}
+
+fn bench_program(c: &mut Criterion, name: &str, mode: Mode, hires: bool, program: &[u16]){
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    group.bench_function(format!("{:?}", mode).to_lowercase(), |b| {
+        let mut machine = if hires { Machine::hires(mode) } else { Machine::new(mode) };
+        machine.cpu.load_memory(PROGRAM_START, &rom(program));
+        b.iter(|| {
+            machine.steps(black_box(INSTRUCTIONS));
+        });
+    });
+    group.finish();
+}
+
+fn fetch_decode_execute(c: &mut Criterion){
+    bench_program(c, "arithmetic", Mode::Chip8, false, ARITHMETIC);
+}
+
+fn sprites(c: &mut Criterion){
+    bench_program(c, "dxy0_lores", Mode::SuperChip, false, SPRITES_16);
+    bench_program(c, "dxy0_hires", Mode::SuperChip, true, SPRITES_16);
+    bench_program(c, "dxyn_lores", Mode::Chip8, false, SPRITES_8);
+    bench_program(c, "dxyn_hires_wrap", Mode::XoChip, true, SPRITES_8);
+}
+
+fn scrolls(c: &mut Criterion){
+    bench_program(c, "scroll_lores", Mode::SuperChip, false, SCROLLS);
+    bench_program(c, "scroll_hires", Mode::SuperChip, true, SCROLLS);
+}
+
+// A whole frame through Chip8, with locking, timers and sound events
+fn tick(c: &mut Criterion){
+    let mut chip8 = Chip8::new(Mode::XoChip);
+    chip8.ipf.store(INSTRUCTIONS as u32, Ordering::Relaxed);
+    chip8.load_rom(&rom(MIXED)).unwrap();
+
+    let mut group = c.benchmark_group("tick_xochip_1000_ipf");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    group.bench_function("xochip", |b| b.iter(|| chip8.run_frames(black_box(1))));
+    group.finish();
+
+    report_budget(&chip8);
+}
+
+// Builds for `cargo test --benches` are not optimized, so their timings are not reported
+fn report_budget(chip8: &Chip8){
+    if !std::env::args().any(|arg| arg == "--bench") {
+        return;
+    }
+
+    let start = Instant::now();
+    chip8.run_frames(BUDGET_FRAMES);
+    let frame_time = start.elapsed() / BUDGET_FRAMES;
+    if frame_time < FRAME_BUDGET {
+        println!("Frame budget: {:?} of {:?}", frame_time, FRAME_BUDGET);
+    } else {
+        println!("Frame budget: {:?} is over the {:?} budget on this machine", frame_time, FRAME_BUDGET);
+    }
+}
+
+criterion_group!(benches, fetch_decode_execute, sprites, scrolls, tick);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use chip8_lib::cpu_state::CpuState;
+
+// Every opcode is either decoded or rejected, decoded ones can be printed
+fuzz_target!(|opcodes: Vec<u16>| {
+    for opcode in opcodes {
+        if let Some(instruction) = CpuState::decode(opcode) {
+            let _ = instruction.to_string();
+        }
+    }
+});
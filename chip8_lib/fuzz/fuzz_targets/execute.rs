@@ -0,0 +1,50 @@
+#![no_main]
+
+use std::sync::atomic::Ordering;
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::cpu_state::Quirk;
+use chip8_lib::parameters::*;
+
+const MODES: [Mode; 4] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip, Mode::Experimental];
+const QUIRKS: [Quirk; 6] = [Quirk::VfReset, Quirk::Shift, Quirk::Jump, Quirk::Memory, Quirk::IOverflow, Quirk::Wrap];
+// Kept low so that every input runs quickly
+const IPF: u32 = 50;
+const MAX_FRAMES: usize = 64;
+
+#[derive(Arbitrary, Debug)]
+struct Input{
+    mode: u8,
+    quirks: [Option<bool>; 6],
+    hires: bool,
+    rom: Vec<u8>,
+    // The keys held in each frame, bit N for key N
+    frames: Vec<u16>,
+}
+
+// Any ROM with any input runs without panicking and leaves the machine in a valid state
+fuzz_target!(|input: Input| {
+    let mut chip8 = Chip8::new(MODES[input.mode as usize % MODES.len()]);
+    for (quirk, enabled) in QUIRKS.into_iter().zip(input.quirks) {
+        if let Some(enabled) = enabled {
+            chip8.set_quirk(quirk, enabled);
+        }
+    }
+    chip8.ipf.store(IPF, Ordering::Relaxed);
+    chip8.hires_mode.store(input.hires, Ordering::Relaxed);
+    chip8.load_font_into_memory();
+    if chip8.load_rom(&input.rom).is_err() {
+        return;
+    }
+
+    for &keys in input.frames.iter().take(MAX_FRAMES) {
+        chip8.set_keys_mask(keys);
+        chip8.run_frames(1);
+
+        let cpu = chip8.state.lock().unwrap();
+        assert!(cpu.pc < MEMORY_SIZE);
+        assert!(cpu.stack.len() <= STACK_SIZE);
+        assert!(cpu.awaiting_key.is_none_or(|key| key < 16));
+    }
+});
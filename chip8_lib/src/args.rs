@@ -0,0 +1,11 @@
+use std::str::FromStr;
+
+// Command line parsing shared by the frontends and tools
+
+pub fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String>{
+    args.next().ok_or(format!("Missing value for {}", option))
+}
+
+pub fn parse_number<T: FromStr>(value: &str, option: &str) -> Result<T, String>{
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
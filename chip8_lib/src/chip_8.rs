@@ -1,14 +1,29 @@
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::{fs, thread};
+use std::thread::JoinHandle;
 use std::path::PathBuf;
+use std::fmt;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use crate::chip_8;
-use crate::cpu_state::CpuState;
+use crate::cpu_state::{CpuState, Quirk};
 use crate::display::Display;
-use crate::keypad::KeyPad;
+use crate::instructions::Instruction;
+use crate::keypad::{keys_from_mask, KeyPad};
+use crate::palette::Palette;
+use crate::screenshot::Screenshot;
+use crate::sound::{SoundEvents, SoundRegisters, TICK_NS};
+use crate::triple_buffer::TripleBuffer;
 use crate::parameters::*;
 
+pub const MIN_SPEED: f32 = 0.1;
+pub const MAX_SPEED: f32 = 16.0;
+// Runs ticks back to back as fast as the host allows
+pub const UNCAPPED_SPEED: f32 = f32::INFINITY;
+// How far a frontend may fall behind before its tick clock starts over instead of catching up
+pub const MAX_TICK_LAG_NS: u64 = 100_000_000;
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Mode{
     Chip8,
@@ -16,15 +31,80 @@ pub enum Mode{
     XoChip,
     Experimental
 }
+
+impl FromStr for Mode{
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Mode, String>{
+        match name.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Mode::Chip8),
+            "schip" | "superchip" | "super-chip" => Ok(Mode::SuperChip),
+            "xochip" | "xo-chip" => Ok(Mode::XoChip),
+            "experimental" => Ok(Mode::Experimental),
+            _ => Err(format!("Unknown mode: {}", name))
+        }
+    }
+}
+
+impl fmt::Display for Mode{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Mode::Chip8 => write!(f, "CHIP-8"),
+            Mode::SuperChip => write!(f, "SUPER-CHIP"),
+            Mode::XoChip => write!(f, "XO-CHIP"),
+            Mode::Experimental => write!(f, "Experimental"),
+        }
+    }
+}
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum HaltReason{
+    Exit,        // 00FD
+    ZeroOpcode,  // 0000, usually running past the end of the program
+}
+
+impl HaltReason{
+    pub fn from_instruction(instruction: &Instruction) -> Option<HaltReason>{
+        match instruction {
+            Instruction::I00FD => Some(HaltReason::Exit),
+            Instruction::I0000 => Some(HaltReason::ZeroOpcode),
+            _ => None
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Lifecycle{
+    Idle,    // not started yet, or stopped
+    Running,
+    Paused,
+    Halted(HaltReason),
+}
+
+// Called by the execution thread at the start of every tick with the tick number and the pressed keys,
+// returns the keys the tick runs with. Movies record and replay their input through it.
+pub type InputHook = Box<dyn FnMut(u64, u16) -> u16 + Send>;
+
+// Displays with the ticks run until each of them
+pub type CapturedFrames = Vec<(u64, Display)>;
+
 pub struct Chip8{
     pub state: Arc<Mutex<CpuState>>,
-    pub display: Arc<Mutex<Display>>,
+    pub display: Arc<Mutex<Display>>, // drawn on by the execution thread
+    pub frames: Arc<TripleBuffer<(u64, Display)>>, // the display after every tick and the ticks run until then, for the frontend
     pub running: Arc<AtomicBool>,
-    pub keys: Arc<Mutex<[bool; 16]>>,
+    pub keys: Arc<AtomicU16>, // bit N is set when key N is pressed
     pub hires_mode: Arc<AtomicBool>,
-    pub fps_ns: Arc<AtomicU64>, //in nano seconds
+    pub speed: Arc<AtomicU32>, // f32 bits, 1.0 runs the machine at its real 60 Hz
     pub ipf: Arc<AtomicU32>,
-    pub compatibility_mode: Arc<Mutex<Mode>>
+    pub compatibility_mode: Arc<Mutex<Mode>>,
+    pub sound_events: Arc<Mutex<SoundEvents>>,
+    pub ticks: Arc<AtomicU64>, // 60 Hz ticks run since the start
+    pub lifecycle: Arc<Mutex<Lifecycle>>,
+    pub input_hook: Arc<Mutex<Option<InputHook>>>,
+    pub captured_frames: Arc<Mutex<Option<CapturedFrames>>>, // the display after every tick while capturing, for recordings
+    display_reset: Arc<AtomicBool>, // asks the execution thread to blank the display
+    execution_thread: Option<JoinHandle<()>>,
+    rom: Vec<u8>, // kept for hard resets
 }
 
 impl Chip8{
@@ -32,23 +112,37 @@ impl Chip8{
         let mut chip_8 = Chip8{
             state: Arc::new(Mutex::new(CpuState::default())),
             display: Arc::new(Mutex::new(Display::new())),
+            frames: Arc::new(TripleBuffer::new((0, Display::new()))),
             running: Arc::new(AtomicBool::new(true)),
-            keys: Arc::new(Mutex::new([false; 16])),
+            keys: Arc::new(AtomicU16::new(0)),
             hires_mode: Arc::new(AtomicBool::new(false)),
-            fps_ns: Arc::new(AtomicU64::new(16_666_667)),
+            speed: Arc::new(AtomicU32::new(1.0f32.to_bits())),
             ipf: Arc::new(AtomicU32::new(100)),
             compatibility_mode: Arc::new(Mutex::new(mode)),
+            sound_events: Arc::new(Mutex::new(SoundEvents::default())),
+            ticks: Arc::new(AtomicU64::new(0)),
+            lifecycle: Arc::new(Mutex::new(Lifecycle::Idle)),
+            input_hook: Arc::new(Mutex::new(None)),
+            captured_frames: Arc::new(Mutex::new(None)),
+            display_reset: Arc::new(AtomicBool::new(false)),
+            execution_thread: None,
+            rom: vec![],
         };
         chip_8.set_compatibility_mode(&mode);
         chip_8
     }
 
-    pub fn get_new_and_start(rom_file: &PathBuf, mode: Mode) -> Chip8{
+    pub fn get_new_and_start(rom_file: &PathBuf, mode: Mode) -> Result<Chip8, String>{
         let mut chip8 = Chip8::new(mode);
-        chip8.start(rom_file);
-        chip8
+        chip8.start(rom_file)?;
+        Ok(chip8)
     }
 
+    pub fn get_compatibility_mode(&self) -> Mode{
+        *self.compatibility_mode.lock().unwrap()
+    }
+
+    // Replaces the quirks and ipf with the ones of the mode, the running program carries on
     pub fn set_compatibility_mode(&mut self, mode: &Mode){
         let mut current_compatibility = self.compatibility_mode.lock().unwrap();
         *current_compatibility = *mode;
@@ -63,99 +157,345 @@ impl Chip8{
         }
     }
 
+    pub fn set_quirk(&mut self, quirk: Quirk, enabled: bool){
+        let mut cpu = self.state.lock().unwrap();
+        cpu.set_quirk(quirk, enabled);
+    }
+
+    pub fn get_quirk(&self, quirk: Quirk) -> bool{
+        self.state.lock().unwrap().get_quirk(quirk)
+    }
+
+    pub fn screenshot(&self, palette: &Palette, scale: u32) -> Screenshot{
+        Screenshot::capture(&self.get_display(), palette, self.hires_mode.load(Ordering::Relaxed), scale)
+    }
+
+    // The display as of the last finished tick, never waits for the execution thread
+    pub fn get_display(&self) -> Display{
+        self.frames.read().1
+    }
+
+    // Like get_display, with the number of ticks run until then
+    pub fn get_frame(&self) -> (u64, Display){
+        self.frames.read()
+    }
+
+    pub fn get_speed(&self) -> f32{
+        f32::from_bits(self.speed.load(Ordering::Relaxed))
+    }
+
+    // Speed multiplier for turbo and slow motion, timers and instructions are scaled together
+    pub fn set_speed(&self, speed: f32){
+        let speed = if speed == UNCAPPED_SPEED { speed } else { speed.clamp(MIN_SPEED, MAX_SPEED) };
+        self.speed.store(speed.to_bits(), Ordering::Relaxed);
+    }
+
+    // Wall clock time of one 60 Hz tick at the current speed
+    pub fn get_tick_interval_ns(speed: &AtomicU32) -> u64{
+        (TICK_NS as f64 / f32::from_bits(speed.load(Ordering::Relaxed)) as f64) as u64
+    }
+
+    pub fn get_lifecycle(&self) -> Lifecycle{
+        *self.lifecycle.lock().unwrap()
+    }
+
+    // Set before starting to see every tick
+    pub fn set_input_hook(&self, hook: Option<InputHook>){
+        *self.input_hook.lock().unwrap() = hook;
+    }
+
+    // Keeps the display of every tick from now on, the frontend presents only some of them
+    pub fn start_frame_capture(&self){
+        *self.captured_frames.lock().unwrap() = Some(vec![]);
+    }
+
+    pub fn stop_frame_capture(&self){
+        *self.captured_frames.lock().unwrap() = None;
+    }
+
+    // The frames captured since the last call, with the ticks run until each of them
+    pub fn take_captured_frames(&self) -> CapturedFrames{
+        self.captured_frames.lock().unwrap().as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // Waits for the execution thread to finish its tick, so nothing runs once this returns
     pub fn stop(&mut self) {
         self.running.store(false, Ordering::Relaxed);
+        if let Some(Err(_)) = self.execution_thread.take().map(|execution_thread| execution_thread.join()) {
+            println!("Execution thread panicked");
+        }
+
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        if matches!(*lifecycle, Lifecycle::Running | Lifecycle::Paused) {
+            *lifecycle = Lifecycle::Idle;
+        }
+    }
+
+    // Timers and instructions stop until resume, the sound is silenced
+    pub fn pause(&mut self){
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        if *lifecycle != Lifecycle::Running {
+            return;
+        }
+        *lifecycle = Lifecycle::Paused;
+
+        let mode = *self.compatibility_mode.lock().unwrap();
+        let time_ns = self.ticks.load(Ordering::Relaxed) * TICK_NS;
+        self.sound_events.lock().unwrap().push(time_ns, SoundRegisters::silent(mode));
+    }
+
+    pub fn resume(&mut self){
+        let mut lifecycle = self.lifecycle.lock().unwrap();
+        if *lifecycle == Lifecycle::Paused {
+            *lifecycle = Lifecycle::Running;
+        }
+    }
+
+    // Restarts the program without touching memory, like the reset button of a real machine
+    pub fn reset(&mut self){
+        self.restart(|cpu| cpu.reset());
+    }
+
+    // Clears memory and loads the font and ROM again, like switching the machine off and on
+    pub fn hard_reset(&mut self){
+        let rom = self.rom.clone();
+        self.restart(|cpu| {
+            cpu.hard_reset();
+            cpu.load_font();
+            cpu.load_memory(PROGRAM_START, &rom);
+        });
+    }
+
+    // Quirks, ipf and speed are kept. A started machine runs again, even after halting,
+    // and stays paused if it was.
+    fn restart(&mut self, reset_cpu: impl FnOnce(&mut CpuState)){
+        let started = self.execution_thread.is_some();
+        let paused = self.get_lifecycle() == Lifecycle::Paused;
+        self.stop();
+
+        reset_cpu(&mut self.state.lock().unwrap());
+        self.reset_display();
+        *self.lifecycle.lock().unwrap() = Lifecycle::Idle;
+        self.running.store(true, Ordering::Relaxed);
+
+        if started {
+            self.start_execution_thread();
+            if paused {
+                self.pause();
+            }
+        }
+    }
+
+    // Back to a blank lores screen, the program carries on drawing. Only one thread may write the
+    // frames, so while the execution thread runs it blanks the display before its next tick.
+    pub fn reset_display(&self){
+        if self.execution_thread.is_some() {
+            self.display_reset.store(true, Ordering::Relaxed);
+        } else {
+            self.core().reset_display();
+        }
     }
 
-    pub fn start(&mut self, rom_file: &PathBuf){
+    // Nothing runs when the ROM can't be loaded
+    pub fn start(&mut self, rom_file: &PathBuf) -> Result<(), String>{
         self.load_font_into_memory();
-        self.load_cartridge(rom_file);
-        self.start_timer_thread();
+        self.load_cartridge(rom_file)?;
         self.start_execution_thread();
+        Ok(())
     }
 
     pub fn load_font_into_memory(&self){
-        let mut state = self.state.lock().unwrap();
+        self.state.lock().unwrap().load_font();
+    }
 
-        for i in 0..FONT_DATA.len(){
-            state.memory[FONT_MEMORY_START + i] = FONT_DATA[i];
-        }
+    pub fn load_cartridge(&mut self, rom_file: &PathBuf) -> Result<(), String>{
+        let rom = fs::read(rom_file).map_err(|e| format!("Could not read {}: {}", rom_file.display(), e))?;
+        self.load_rom(&rom)
+    }
 
-        for i in 0..BIG_FONT_DATA.len(){
-            state.memory[FONT_MEMORY_START + FONT_DATA.len() + i] = BIG_FONT_DATA[i];
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String>{
+        let max_size = MEMORY_SIZE - PROGRAM_START;
+        if rom.len() > max_size {
+            return Err(format!("ROM is too big: {} bytes, at most {} fit in memory", rom.len(), max_size));
         }
-    }
 
-    pub fn load_cartridge(&mut self, rom_file: &PathBuf){
         let mut state = self.state.lock().unwrap();
+        state.load_memory(PROGRAM_START, rom);
+        self.rom = rom.to_vec();
+        Ok(())
+    }
 
-        let file = fs::read(rom_file);
-        if let Ok(rom) = file{
-            for (i, byte) in rom.iter().enumerate(){
-                state.memory[0x200 + i] = *byte;
+    // Runs the given number of ticks on the calling thread, without waiting between them,
+    // nothing runs while paused. For headless runs, tests and frontends without threads,
+    // the execution thread must not be started.
+    pub fn run_frames(&self, frames: u32){
+        self.run_frames_traced(frames, |_| {});
+    }
+
+    // Like run_frames, on_instruction sees the state before every instruction
+    pub fn run_frames_traced(&self, frames: u32, mut on_instruction: impl FnMut(&CpuState)){
+        let core = self.core();
+        for _ in 0..frames {
+            if !self.running.load(Ordering::Relaxed) || self.get_lifecycle() == Lifecycle::Paused {
+                break;
             }
-        }
-        else{
-            panic!("Could not read ROM file");
+            core.run_tick(&mut on_instruction);
         }
     }
 
-    fn start_timer_thread(&mut self) {
-        let state = Arc::clone(&self.state);
-        let running = Arc::clone(&self.running);
-        let fps = Arc::clone(&self.fps_ns);
+    fn core(&self) -> Core{
+        Core{
+            state: Arc::clone(&self.state),
+            display: Arc::clone(&self.display),
+            frames: Arc::clone(&self.frames),
+            running: Arc::clone(&self.running),
+            keys: Arc::clone(&self.keys),
+            hires_mode: Arc::clone(&self.hires_mode),
+            speed: Arc::clone(&self.speed),
+            ipf: Arc::clone(&self.ipf),
+            compatibility_mode: Arc::clone(&self.compatibility_mode),
+            sound_events: Arc::clone(&self.sound_events),
+            ticks: Arc::clone(&self.ticks),
+            lifecycle: Arc::clone(&self.lifecycle),
+            input_hook: Arc::clone(&self.input_hook),
+            captured_frames: Arc::clone(&self.captured_frames),
+            display_reset: Arc::clone(&self.display_reset),
+        }
+    }
 
-        thread::spawn(move || {
-            while running.load(Ordering::Relaxed) {
-                let start = Instant::now();
+    fn start_execution_thread(&mut self) {
+        let core = self.core();
+        *self.lifecycle.lock().unwrap() = Lifecycle::Running;
 
-                {
-                    let mut cpu_state = state.lock().unwrap();
-                    cpu_state.delay_timer = cpu_state.delay_timer.saturating_sub(1u8);
-                    cpu_state.sound_timer = cpu_state.sound_timer.saturating_sub(1u8);
+        self.execution_thread = Some(thread::spawn(move || {
+            let mut next_tick = Instant::now();
+            while core.running.load(Ordering::Relaxed) {
+                if core.display_reset.swap(false, Ordering::Relaxed) {
+                    core.reset_display();
+                }
+                if *core.lifecycle.lock().unwrap() != Lifecycle::Paused {
+                    core.run_tick(&mut |_| {});
                 }
 
-                let elapsed = start.elapsed().as_nanos() as u64;
-                thread::sleep(Duration::from_nanos(fps.load(Ordering::Relaxed).saturating_sub(elapsed)));
-            }
-        });
-    }
-    
-    fn start_execution_thread(&mut self) {
-        let display = Arc::clone(&self.display);
-        let state = Arc::clone(&self.state);
-        let running = Arc::clone(&self.running);
-        let keys = Arc::clone(&self.keys);
-        let hires_mode = Arc::clone(&self.hires_mode);
-        let ipf = Arc::clone(&self.ipf);
-        let fps = Arc::clone(&self.fps_ns);
-
-        thread::spawn(move || {
-            while running.load(Ordering::Relaxed) {
-                let start = Instant::now();
-                {
-                    let mut cpu_state = state.lock().unwrap();
-                    let mut display = display.lock().unwrap();
-                    let keys = keys.lock().unwrap();
-
-                    for _ in 0..ipf.load(Ordering::Relaxed){
-                        if let Some(instruction) = cpu_state.get_current_instruction(true){
-                            instruction.execute(&mut cpu_state, &mut display, &keys, hires_mode.as_ref(), running.as_ref());
-                        }
+                // Ticks are scheduled on a fixed clock so sleep inaccuracies don't add up,
+                // after a long stall the clock starts over instead of catching up
+                next_tick += Duration::from_nanos(Self::get_tick_interval_ns(&core.speed));
+                let now = Instant::now();
+                if next_tick > now {
+                    thread::sleep(next_tick - now);
+                } else {
+                    if now - next_tick > Duration::from_nanos(MAX_TICK_LAG_NS) {
+                        next_tick = now;
                     }
+                    // Uncapped, give the frontend a chance to run
+                    thread::yield_now();
                 }
-                let elapsed = start.elapsed().as_nanos() as u64;
-                thread::sleep(Duration::from_nanos(fps.load(Ordering::Relaxed).saturating_sub(elapsed)));
             }
-        });
+        }));
     }
 
     pub fn handle_input(&mut self, pressed_key: KeyPad, pressed: bool){
-        let mut keys = self.keys.lock().unwrap();
-        keys[pressed_key as usize] = pressed;
+        let bit = 1 << pressed_key as usize;
+        if pressed {
+            self.keys.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            self.keys.fetch_and(!bit, Ordering::Relaxed);
+        }
+    }
+
+    // Bit N of the mask is set when key N is pressed
+    pub fn get_keys_mask(&self) -> u16{
+        self.keys.load(Ordering::Relaxed)
     }
 
+    pub fn set_keys_mask(&mut self, mask: u16){
+        self.keys.store(mask, Ordering::Relaxed);
+    }
+
+}
+
+// The parts of the machine a tick works on. The execution thread gets its own copy,
+// which has nothing to stop or join when it is dropped.
+struct Core{
+    state: Arc<Mutex<CpuState>>,
+    display: Arc<Mutex<Display>>,
+    frames: Arc<TripleBuffer<(u64, Display)>>,
+    running: Arc<AtomicBool>,
+    keys: Arc<AtomicU16>,
+    hires_mode: Arc<AtomicBool>,
+    speed: Arc<AtomicU32>,
+    ipf: Arc<AtomicU32>,
+    compatibility_mode: Arc<Mutex<Mode>>,
+    sound_events: Arc<Mutex<SoundEvents>>,
+    ticks: Arc<AtomicU64>,
+    lifecycle: Arc<Mutex<Lifecycle>>,
+    input_hook: Arc<Mutex<Option<InputHook>>>,
+    captured_frames: Arc<Mutex<Option<CapturedFrames>>>,
+    display_reset: Arc<AtomicBool>,
 }
 
+impl Core{
+    fn reset_display(&self){
+        *self.display.lock().unwrap() = Display::new();
+        self.frames.write(&(self.ticks.load(Ordering::Relaxed), Display::new()));
+        self.hires_mode.store(false, Ordering::Relaxed);
+    }
 
+    // One timer tick: the timers count down, then ipf instructions run.
+    // Sound register changes are stamped with the emulated time of the instruction that made them.
+    fn run_tick(&self, on_instruction: &mut impl FnMut(&CpuState)){
+        let mode = *self.compatibility_mode.lock().unwrap();
+        let mut cpu_state = self.state.lock().unwrap();
+        let mut display = self.display.lock().unwrap();
+        let tick = self.ticks.fetch_add(1, Ordering::Relaxed);
+
+        // Keys pressed during the tick are seen from the next one
+        let mut keys_mask = self.keys.load(Ordering::Relaxed);
+        if let Some(hook) = self.input_hook.lock().unwrap().as_mut() {
+            let hooked_mask = hook(tick, keys_mask);
+            if hooked_mask != keys_mask {
+                self.keys.store(hooked_mask, Ordering::Relaxed);
+                keys_mask = hooked_mask;
+            }
+        }
+        let keys = keys_from_mask(keys_mask);
+        // Collected here and handed over at the end, so the frontend never waits a whole tick for the lock
+        let mut sound_events = SoundEvents::default();
 
+        cpu_state.delay_timer = cpu_state.delay_timer.saturating_sub(1u8);
+        cpu_state.sound_timer = cpu_state.sound_timer.saturating_sub(1u8);
+
+        let tick_ns = tick * TICK_NS;
+        sound_events.push(tick_ns, SoundRegisters::from_state(&cpu_state, mode));
+
+        let ipf = self.ipf.load(Ordering::Relaxed);
+        for n in 0..ipf {
+            // Nothing runs after 00FD
+            if !self.running.load(Ordering::Relaxed) {
+                break;
+            }
+
+            on_instruction(&cpu_state);
+            if let Some(instruction) = cpu_state.get_current_instruction(true){
+                instruction.execute(&mut cpu_state, &mut display, &keys, self.hires_mode.as_ref(), self.running.as_ref());
+                if let Some(reason) = HaltReason::from_instruction(&instruction) {
+                    *self.lifecycle.lock().unwrap() = Lifecycle::Halted(reason);
+                }
+            }
+            let time_ns = tick_ns + TICK_NS * n as u64 / ipf as u64;
+            sound_events.push_if_changed(time_ns, SoundRegisters::from_state(&cpu_state, mode));
+        }
+
+        self.sound_events.lock().unwrap().append(&mut sound_events);
+        if let Some(captured_frames) = self.captured_frames.lock().unwrap().as_mut() {
+            captured_frames.push((tick + 1, *display));
+        }
+        self.frames.write(&(tick + 1, *display));
+    }
+}
+
+impl Drop for Chip8{
+    fn drop(&mut self){
+        self.stop();
+    }
+}
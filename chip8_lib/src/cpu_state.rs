@@ -1,14 +1,83 @@
+use std::fmt;
+use std::str::FromStr;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use crate::chip_8::Mode;
 use crate::decoded_instruction::DecodedInstruction;
 use crate::instructions::Instruction;
+use crate::parameters::*;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Quirk{
+    VfReset,      // 8XY1, 8XY2, 8XY3 reset VF
+    Shift,        // 8XY6, 8XYE shift VX in place, ignoring VY
+    Jump,         // BNNN jumps to NNN + VX instead of NNN + V0
+    Memory,       // FX55, FX65 increment I
+    IOverflow,    // FX1E sets VF on I overflow
+    Wrap,         // sprites wrap around the screen edges instead of clipping
+}
+
+impl FromStr for Quirk{
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Quirk, String>{
+        match name.to_lowercase().as_str() {
+            "vf-reset" => Ok(Quirk::VfReset),
+            "shift" => Ok(Quirk::Shift),
+            "jump" => Ok(Quirk::Jump),
+            "memory" => Ok(Quirk::Memory),
+            "i-overflow" => Ok(Quirk::IOverflow),
+            "wrap" => Ok(Quirk::Wrap),
+            _ => Err(format!("Unknown quirk: {}", name))
+        }
+    }
+}
+
+impl fmt::Display for Quirk{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Quirk::VfReset => write!(f, "vf-reset"),
+            Quirk::Shift => write!(f, "shift"),
+            Quirk::Jump => write!(f, "jump"),
+            Quirk::Memory => write!(f, "memory"),
+            Quirk::IOverflow => write!(f, "i-overflow"),
+            Quirk::Wrap => write!(f, "wrap"),
+        }
+    }
+}
+
+impl Quirk{
+    pub const ALL: [Quirk; 6] = [Quirk::VfReset, Quirk::Shift, Quirk::Jump, Quirk::Memory, Quirk::IOverflow, Quirk::Wrap];
+
+    // "<name>=<on|off>", e.g. "shift=off"
+    pub fn parse_setting(value: &str) -> Result<(Quirk, bool), String>{
+        let (name, state) = value.split_once('=').ok_or(format!("Invalid quirk override: {}, expected <NAME>=<on|off>", value))?;
+
+        let enabled = match state {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("Invalid quirk state: {}", state)),
+        };
+
+        Ok((name.parse()?, enabled))
+    }
+}
+
+// Decoded instructions by address, so loops don't decode the same opcodes on every step
+#[derive(Copy, Clone)]
+enum CachedInstruction{
+    Empty,
+    Invalid,
+    Decoded(Instruction),
+}
 
 pub struct CpuState {
-    pub memory: [u8; 65_536],
+    memory: [u8; MEMORY_SIZE],
     pub pc: usize,
     pub i: u16,
     pub stack: Vec<u16>,
     pub registers: [u8; 16], // named V0 through VF , VF - is a carry flag
-    pub rpl_flags: [u8; 8],
+    pub rpl_flags: [u8; 16],
     pub delay_timer: u8,
     pub sound_timer: u8,
     pub sound_pattern_buffer: [u8; 16],
@@ -20,13 +89,15 @@ pub struct CpuState {
     pub alt_8XY123: bool,
     pub alt_IFX1E: bool,
     pub alt_allow_scrolling: bool,
+    decode_cache: Vec<CachedInstruction>,
+    rng: StdRng, // for CXNN
 }
 
 impl Default for CpuState {
     fn default() -> CpuState {
         CpuState{
-            memory: [0; 65_536],
-            pc: 0x200,
+            memory: [0; MEMORY_SIZE],
+            pc: PROGRAM_START,
             i: 0,
             stack: vec![],
             delay_timer: 0,
@@ -34,7 +105,7 @@ impl Default for CpuState {
             sound_pattern_buffer: [0; 16],
             pitch_register: 64, //64 = 4000 HZ, 4000*2^((vx-64)/48)
             registers: [0; 16],
-            rpl_flags: [0; 8],
+            rpl_flags: [0; 16],
             awaiting_key: None,
             alt_8XY6_8XYE: false,
             alt_BNNN: false,
@@ -42,20 +113,29 @@ impl Default for CpuState {
             alt_8XY123: false,
             alt_IFX1E: false,
             alt_allow_scrolling: false,
+            decode_cache: vec![CachedInstruction::Empty; MEMORY_SIZE],
+            rng: StdRng::from_os_rng(),
         }
     }
 }
 
 
 impl CpuState{
+    // Quirks left over from the previous mode are cleared first
     pub fn set_compatibility_mode(&mut self, mode: &Mode){
+        self.alt_8XY6_8XYE = false;
+        self.alt_BNNN = false;
+        self.alt_FX55_FX65 = false;
+        self.alt_8XY123 = false;
+        self.alt_IFX1E = false;
+        self.alt_allow_scrolling = false;
+
         match mode {
             Mode::Chip8 => {
                 self.alt_8XY123 = true;
                 self.alt_FX55_FX65 = true;
             }
             Mode::SuperChip => {
-                self.alt_allow_scrolling = false;
                 self.alt_8XY6_8XYE = true;
                 self.alt_BNNN = true;
             }
@@ -64,16 +144,105 @@ impl CpuState{
                 self.alt_FX55_FX65 = true;
             }
             Mode::Experimental => {
-                self.alt_FX55_FX65 = false;
                 self.alt_allow_scrolling = true;
             }
         }
     }
 
+    pub fn set_quirk(&mut self, quirk: Quirk, enabled: bool){
+        match quirk {
+            Quirk::VfReset => self.alt_8XY123 = enabled,
+            Quirk::Shift => self.alt_8XY6_8XYE = enabled,
+            Quirk::Jump => self.alt_BNNN = enabled,
+            Quirk::Memory => self.alt_FX55_FX65 = enabled,
+            Quirk::IOverflow => self.alt_IFX1E = enabled,
+            Quirk::Wrap => self.alt_allow_scrolling = enabled,
+        }
+    }
+
+    pub fn get_quirk(&self, quirk: Quirk) -> bool{
+        match quirk {
+            Quirk::VfReset => self.alt_8XY123,
+            Quirk::Shift => self.alt_8XY6_8XYE,
+            Quirk::Jump => self.alt_BNNN,
+            Quirk::Memory => self.alt_FX55_FX65,
+            Quirk::IOverflow => self.alt_IFX1E,
+            Quirk::Wrap => self.alt_allow_scrolling,
+        }
+    }
+
+    // Like pressing reset: the registers, timers and stack are cleared, memory and the quirks are kept
+    pub fn reset(&mut self){
+        self.pc = PROGRAM_START;
+        self.i = 0;
+        self.stack.clear();
+        self.registers = [0; 16];
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.sound_pattern_buffer = [0; 16];
+        self.pitch_register = 64;
+        self.awaiting_key = None;
+    }
+
+    // Like switching the machine off and on, memory and the RPL flags are cleared as well
+    pub fn hard_reset(&mut self){
+        self.reset();
+        self.memory = [0; MEMORY_SIZE];
+        self.rpl_flags = [0; 16];
+        self.decode_cache.fill(CachedInstruction::Empty);
+    }
+
+    // The same seed gives the same CXNN values, so runs can be repeated
+    pub fn seed_rng(&mut self, seed: u64){
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn random_byte(&mut self) -> u8{
+        self.rng.random()
+    }
+
+    // Read only, writes go through write_byte or load_memory
+    pub fn memory(&self) -> &[u8]{
+        &self.memory
+    }
+
+    // Addresses past the end of memory wrap around to the start
+    pub fn read_byte(&self, address: usize) -> u8{
+        self.memory[address % MEMORY_SIZE]
+    }
+
+    pub fn write_byte(&mut self, address: usize, value: u8){
+        let address = address % MEMORY_SIZE;
+        self.memory[address] = value;
+        self.invalidate(address);
+    }
+
+    pub fn load_font(&mut self){
+        self.load_memory(FONT_MEMORY_START, &FONT_DATA);
+        self.load_memory(FONT_MEMORY_START + FONT_DATA.len(), &BIG_FONT_DATA);
+    }
+
+    // Writes from outside of instructions (fonts, ROMs) must go through here to keep the decode cache valid
+    pub fn load_memory(&mut self, address: usize, bytes: &[u8]){
+        for (idx, &byte) in bytes.iter().enumerate() {
+            self.write_byte(address + idx, byte);
+        }
+    }
+
+    // The instructions starting at the address and one byte before it both contain it
+    fn invalidate(&mut self, address: usize){
+        self.decode_cache[address] = CachedInstruction::Empty;
+        self.decode_cache[(address + MEMORY_SIZE - 1) % MEMORY_SIZE] = CachedInstruction::Empty;
+    }
+
+    pub fn advance_pc(&mut self, bytes: usize){
+        self.pc = (self.pc + bytes) % MEMORY_SIZE;
+    }
+
     pub fn fetch(&mut self) -> u16{
         let pc = self.pc;
 
-        let instruction: u16 = ((self.memory[pc] as u16) << 8) | (self.memory[pc+1] as u16);
+        let instruction: u16 = ((self.read_byte(pc) as u16) << 8) | (self.read_byte(pc + 1) as u16);
 
         instruction
     }
@@ -94,18 +263,31 @@ impl CpuState{
     }
 
     pub fn get_current_instruction(&mut self, increment_pc: bool) -> Option<Instruction> {
-        let instruction = self.fetch();
+        let pc = self.pc;
+        let instruction = match self.decode_cache[pc] {
+            CachedInstruction::Decoded(instruction) => Some(instruction),
+            CachedInstruction::Invalid => None,
+            CachedInstruction::Empty => {
+                let instruction = Self::decode(self.fetch());
+                self.decode_cache[pc] = match instruction {
+                    Some(instruction) => CachedInstruction::Decoded(instruction),
+                    None => CachedInstruction::Invalid,
+                };
+                instruction
+            }
+        };
+
         if increment_pc{
-            self.pc += 2;
+            self.advance_pc(2);
         }
-        Self::decode(instruction)
+        instruction
     }
 
     pub fn skip_instruction(&mut self) {
         if let Some(instruction) = self.get_current_instruction(false) {
             match instruction{
-                Instruction::IF000 => self.pc += 4,
-                _ => self.pc +=2
+                Instruction::IF000 => self.advance_pc(4),
+                _ => self.advance_pc(2)
             }
         }
         else{
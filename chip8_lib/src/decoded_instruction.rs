@@ -15,7 +15,7 @@ impl DecodedInstruction {
         /*I00FB,
         I00FC,*/
         match *self{
-            //DecodedInstruction {opcode: 0x0, x: 0x0, y: 0x0, n: 0x0, ..} => Some(Instruction::I0000),
+            DecodedInstruction {opcode: 0x0, x: 0x0, y: 0x0, n: 0x0, ..} => Some(Instruction::I0000),
             DecodedInstruction {opcode: 0x0, x: 0x0, y: 0xB, n, ..} => Some(Instruction::I00BN {n}),
             DecodedInstruction {opcode: 0x0, x: 0x0, y: 0xD, n, ..} => Some(Instruction::I00DN {n}),
             DecodedInstruction {opcode: 0x0, x: 0x0, y: 0xC, n, ..} => Some(Instruction::I00CN {n}),
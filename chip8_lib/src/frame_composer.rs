@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use crate::display::Display;
+use crate::palette::{Palette, Rgb};
+use crate::parameters::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_FLICKER_FRAMES: usize = 2;
+pub const MAX_FLICKER_FRAMES: usize = 8;
+
+// How the last frames are combined to hide sprites being erased and redrawn:
+// Or lights a pixel if it was lit in any of them, Blend averages their colors.
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "filter", rename_all = "snake_case"))]
+pub enum FlickerFilter{
+    #[default]
+    Off,
+    Or{
+        #[cfg_attr(feature = "serde", serde(default = "default_frames"))]
+        frames: usize
+    },
+    Blend{
+        #[cfg_attr(feature = "serde", serde(default = "default_frames"))]
+        frames: usize
+    },
+}
+
+#[cfg(feature = "serde")]
+fn default_frames() -> usize{
+    DEFAULT_FLICKER_FRAMES
+}
+
+// "off", "or", "blend" or with a frame count, e.g. "or:3"
+impl FromStr for FlickerFilter{
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<FlickerFilter, String>{
+        let (name, frames) = match value.split_once(':') {
+            Some((name, frames)) => {
+                let frames = frames.parse().map_err(|_| format!("Invalid frame count: {}", frames))?;
+                (name, frames)
+            },
+            None => (value, DEFAULT_FLICKER_FRAMES),
+        };
+
+        match name.to_lowercase().as_str() {
+            "off" => Ok(FlickerFilter::Off),
+            "or" => Ok(FlickerFilter::Or{ frames }),
+            "blend" => Ok(FlickerFilter::Blend{ frames }),
+            _ => Err(format!("Unknown flicker filter: {}", value))
+        }
+    }
+}
+
+impl FlickerFilter{
+    pub fn frames(&self) -> usize{
+        match self {
+            FlickerFilter::Off => 1,
+            FlickerFilter::Or{frames} | FlickerFilter::Blend{frames} => (*frames).clamp(1, MAX_FLICKER_FRAMES),
+        }
+    }
+}
+
+// Keeps the last displayed frames and combines them for presentation only,
+// the emulated display is never modified.
+#[derive(PartialEq, Clone, Debug)]
+pub struct FrameComposer{
+    filter: FlickerFilter,
+    history: VecDeque<Display>,
+    composed: Display, // the planes of all kept frames ORed together, updated on every change
+    last_tick: Option<u64>,
+}
+
+impl Default for FrameComposer{
+    fn default() -> FrameComposer{
+        FrameComposer::new(FlickerFilter::default())
+    }
+}
+
+impl FrameComposer{
+    pub fn new(filter: FlickerFilter) -> FrameComposer{
+        FrameComposer{
+            filter,
+            history: VecDeque::with_capacity(filter.frames()),
+            composed: Display::new(),
+            last_tick: None,
+        }
+    }
+
+    pub fn filter(&self) -> FlickerFilter{
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: FlickerFilter){
+        self.filter = filter;
+        self.history.truncate(filter.frames());
+        self.recompose();
+    }
+
+    pub fn clear(&mut self){
+        self.history.clear();
+        self.composed = Display::new();
+        self.last_tick = None;
+    }
+
+    // Called with every presented frame and the tick it shows. Frames are presented more often than
+    // ticks run at high frame rates or in slow motion, a tick that is already kept is skipped.
+    pub fn push(&mut self, tick: u64, display: Display){
+        if self.last_tick == Some(tick) {
+            return;
+        }
+        self.last_tick = Some(tick);
+
+        self.history.truncate(self.filter.frames() - 1);
+        self.history.push_front(display);
+        self.recompose();
+    }
+
+    pub fn last_tick(&self) -> Option<u64>{
+        self.last_tick
+    }
+
+    pub fn latest(&self) -> Display{
+        self.history.front().copied().unwrap_or_else(Display::new)
+    }
+
+    // The display with the planes of all kept frames ORed together
+    pub fn compose(&self) -> Display{
+        self.composed
+    }
+
+    fn recompose(&mut self){
+        self.composed = self.latest();
+        for display in self.history.iter().skip(1) {
+            for idx in 0..DISPLAY_SIZE {
+                self.composed.plane_1[idx] |= display.plane_1[idx];
+                self.composed.plane_2[idx] |= display.plane_2[idx];
+            }
+        }
+    }
+
+    pub fn get_pixel_color(&self, palette: &Palette, idx: usize) -> Rgb{
+        let Some(latest) = self.history.front() else {
+            return palette.background;
+        };
+
+        match self.filter {
+            FlickerFilter::Off => palette.get_pixel_color(latest, idx),
+            FlickerFilter::Or{..} => palette.get_pixel_color(&self.composed, idx),
+            FlickerFilter::Blend{..} => {
+                let mut sum = (0u32, 0u32, 0u32);
+                for display in &self.history {
+                    let (r, g, b) = palette.get_pixel_color(display, idx);
+                    sum = (sum.0 + r as u32, sum.1 + g as u32, sum.2 + b as u32);
+                }
+                let count = self.history.len() as u32;
+                ((sum.0 / count) as u8, (sum.1 / count) as u8, (sum.2 / count) as u8)
+            },
+        }
+    }
+}
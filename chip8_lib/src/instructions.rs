@@ -4,7 +4,7 @@ use crate::cpu_state::CpuState;
 use crate::display::Display;
 use crate::parameters::*;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum Instruction {
     I0000,
     I00BN{n: u8},
@@ -165,8 +165,11 @@ impl Instruction {
             }
             Instruction::I1NNN {nnn} => { cpu.pc = nnn as usize; },
             Instruction::I2NNN {nnn} => {
-                cpu.stack.push(cpu.pc as u16);
-                cpu.pc = nnn as usize;
+                // A call that would overflow the stack is ignored
+                if cpu.stack.len() < STACK_SIZE {
+                    cpu.stack.push(cpu.pc as u16);
+                    cpu.pc = nnn as usize;
+                }
             },
             Instruction::I3XNN {x, nn} => {
                 if cpu.registers[x as usize] == nn { cpu.skip_instruction() }
@@ -180,22 +183,22 @@ impl Instruction {
             Instruction::I5XY2 {x, y} => {
                 if x <= y {
                     for (idx,reg) in (x..=y).enumerate(){
-                        cpu.memory[(cpu.i as usize) + idx] = cpu.registers[reg as usize]
+                        cpu.write_byte(cpu.i as usize + idx, cpu.registers[reg as usize])
                     }
                 }else{
                     for (idx,reg) in (y..=x).rev().enumerate(){
-                        cpu.memory[(cpu.i as usize) + idx] = cpu.registers[reg as usize]
+                        cpu.write_byte(cpu.i as usize + idx, cpu.registers[reg as usize])
                     }
                 }
             }
             Instruction::I5XY3 {x, y} => {
                 if x <= y {
                     for (idx,reg) in (x..=y).enumerate(){
-                        cpu.registers[reg as usize] = cpu.memory[(cpu.i as usize) + idx]
+                        cpu.registers[reg as usize] = cpu.read_byte(cpu.i as usize + idx)
                     }
                 }else{
                     for (idx,reg) in (y..=x).rev().enumerate(){
-                        cpu.registers[reg as usize] = cpu.memory[(cpu.i as usize) + idx]
+                        cpu.registers[reg as usize] = cpu.read_byte(cpu.i as usize + idx)
                     }
                 }
             }
@@ -261,7 +264,7 @@ impl Instruction {
                 cpu.pc = value as usize;
             }
             Instruction::ICXNN {x,nn} => {
-                let rand: u8 = rand::random_range(0..=255) & nn;
+                let rand: u8 = cpu.random_byte() & nn;
                 cpu.registers[x as usize] = rand;
             }
             Instruction::IDXY0 {x,y} => {
@@ -279,7 +282,7 @@ impl Instruction {
                     let mut bytes: u16 = 0;
                     for row in 0..16u8*scale {
                         if row % scale == 0 {
-                            bytes = ((cpu.memory[(cpu.i as usize + ((row/scale) * 2) as usize) + (i*32)] as u16) << 8) | (cpu.memory[(cpu.i as usize + (((row/scale) * 2)+ 1) as usize) + (i*32)] as u16);
+                            bytes = ((cpu.read_byte(cpu.i as usize + ((row/scale) * 2) as usize + i*32) as u16) << 8) | (cpu.read_byte(cpu.i as usize + (((row/scale) * 2)+ 1) as usize + i*32) as u16);
                         }
 
                         for col in 0..16u8*scale {
@@ -304,7 +307,7 @@ impl Instruction {
                     let mut byte: u8 = 0;
                     for row in 0..n*scale {
                         if row % scale == 0 {
-                            byte = cpu.memory[(cpu.i as usize + (row/scale) as usize) + (i*(n as usize))];
+                            byte = cpu.read_byte(cpu.i as usize + (row/scale) as usize + i*(n as usize));
                         }
 
                         for col in 0..8u8*scale {
@@ -327,35 +330,39 @@ impl Instruction {
                 }
             }
             Instruction::IF000 => {
-                let nnnn: u16 = ((cpu.memory[cpu.pc] as u16) << 8) | (cpu.memory[cpu.pc+1] as u16);
+                let nnnn: u16 = ((cpu.read_byte(cpu.pc) as u16) << 8) | (cpu.read_byte(cpu.pc + 1) as u16);
                 cpu.i = nnnn;
 
-                cpu.pc += 2;
+                cpu.advance_pc(2);
             }
             Instruction::IFN01 {n} => {
                 display.selected_plane = n;
             }
             Instruction::IF002 => {
                 for i in 0..16 {
-                    cpu.sound_pattern_buffer[i] = cpu.memory[(cpu.i as usize) + i];
+                    cpu.sound_pattern_buffer[i] = cpu.read_byte(cpu.i as usize + i);
                 }
             }
             Instruction::IFX07 {x} => { cpu.registers[x as usize] = cpu.delay_timer }
             Instruction::IFX0A {x} => {
                 let pressed_key = keys.iter().position(|&pressed| pressed);
 
+                let mut released = false;
                 if let Some(awaiting_key) = cpu.awaiting_key{
                     if keys[awaiting_key] == false {
                         cpu.registers[x as usize] = awaiting_key as u8;
                         cpu.awaiting_key = None;
-                        cpu.pc +=2;
+                        released = true;
                     }
                 }
                 else if let Some(pressed_key) = pressed_key{
                     cpu.awaiting_key = Some(pressed_key);
                 }
 
-                cpu.pc -=2;
+                // Steps back onto this instruction until the key is released
+                if !released {
+                    cpu.advance_pc(MEMORY_SIZE - 2);
+                }
             }
             Instruction::IFX15 {x} => { cpu.delay_timer = cpu.registers[x as usize] }
             Instruction::IFX18 {x} => { cpu.sound_timer = cpu.registers[x as usize] }
@@ -377,29 +384,29 @@ impl Instruction {
                 let tens = (value % 100) / 10;
                 let ones = value % 10;
 
-                cpu.memory[cpu.i as usize] = hundred;
-                cpu.memory[cpu.i as usize + 1] = tens;
-                cpu.memory[cpu.i as usize + 2] = ones;
+                cpu.write_byte(cpu.i as usize, hundred);
+                cpu.write_byte(cpu.i as usize + 1, tens);
+                cpu.write_byte(cpu.i as usize + 2, ones);
             }
             Instruction::IFX3A {x} => {
                 cpu.pitch_register = cpu.registers[x as usize];
             }
             Instruction::IFX55 {x} => {
                 for i in 0..=x as usize {
-                    cpu.memory[cpu.i as usize + i] = cpu.registers[i];
+                    cpu.write_byte(cpu.i as usize + i, cpu.registers[i]);
                 }
 
                 if cpu.alt_FX55_FX65{
-                    cpu.i += x as u16 + 1;
+                    cpu.i = cpu.i.wrapping_add(x as u16 + 1);
                 }
             }
             Instruction::IFX65 {x} => {
                 for i in 0..=x as usize {
-                    cpu.registers[i] = cpu.memory[cpu.i as usize + i];
+                    cpu.registers[i] = cpu.read_byte(cpu.i as usize + i);
                 }
 
                 if cpu.alt_FX55_FX65{
-                    cpu.i += x as u16 + 1;
+                    cpu.i = cpu.i.wrapping_add(x as u16 + 1);
                 }
             }
             Instruction::IFX75{x} => {
@@ -494,4 +501,7 @@ impl fmt::Display for Instruction {
             Instruction::IFX85 {x} => {write!(f, "FX85:  V0..={} = pl[i..i+x]",x)}
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1005 @@
+use std::sync::atomic::Ordering;
+use crate::chip_8::Mode;
+use crate::cpu_state::Quirk;
+use crate::display::Display;
+use crate::instructions::Instruction;
+use crate::machine::Machine;
+use crate::parameters::*;
+
+const MODES: [Mode; 4] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip, Mode::Experimental];
+
+// Skips land on a real instruction, skipping over 0x0000 is not possible
+const NEXT: u16 = 0x6E00;
+
+#[test]
+fn i0000_and_00fd_stop_the_machine(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.execute(Instruction::I0000);
+        assert!(!machine.running.load(Ordering::Relaxed), "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.run(0x00FD);
+        assert!(!machine.running.load(Ordering::Relaxed), "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00e0_clears_only_the_selected_planes(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.display.plane_1.fill(true);
+        machine.display.plane_2.fill(true);
+
+        machine.run(0x00E0);
+        assert!(machine.display.plane_1.iter().all(|&on| !on), "{:?}", mode);
+        assert!(machine.display.plane_2.iter().all(|&on| on), "{:?}", mode);
+
+        machine.load(&[0xF301, 0x00E0]);
+        machine.step();
+        machine.step();
+        assert!(machine.display.plane_2.iter().all(|&on| !on), "{:?}", mode);
+    }
+}
+
+#[test]
+fn i2nnn_and_00ee_call_and_return(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.run(0x2300);
+        assert_eq!(machine.cpu.pc, 0x300, "{:?}", mode);
+        assert_eq!(machine.cpu.stack, vec![0x202], "{:?}", mode);
+
+        machine.run(0x2400);
+        assert_eq!(machine.cpu.stack, vec![0x202, 0x302], "{:?}", mode);
+
+        machine.run(0x00EE);
+        assert_eq!(machine.cpu.pc, 0x302, "{:?}", mode);
+        machine.run(0x00EE);
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+        assert!(machine.cpu.stack.is_empty(), "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00ee_with_empty_stack_continues(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.run(0x00EE);
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00fe_and_00ff_switch_resolution_and_clear_both_planes(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.display.plane_1.fill(true);
+        machine.display.plane_2.fill(true);
+
+        machine.run(0x00FF);
+        assert!(machine.hires_mode.load(Ordering::Relaxed), "{:?}", mode);
+        assert_eq!(machine.display, Display::new(), "{:?}", mode);
+
+        machine.display.plane_1.fill(true);
+        machine.display.plane_2.fill(true);
+        machine.run(0x00FE);
+        assert!(!machine.hires_mode.load(Ordering::Relaxed), "{:?}", mode);
+        assert_eq!(machine.display, Display::new(), "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00cn_scrolls_down_half_pixels_in_lores(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_pixel(5, 0);
+        machine.run(0x00C1);
+        assert!(machine.pixel(5, 2), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(5, 0);
+        machine.run(0x00C1);
+        assert!(machine.pixel(5, 1), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(5, DISPLAY_HEIGHT - 1);
+        machine.run(0x00C1);
+        assert_eq!(machine.lit_pixels(), 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00bn_and_00dn_scroll_up(){
+    for mode in MODES {
+        for opcode in [0x00B1, 0x00D1] {
+            let mut machine = Machine::new(mode);
+            machine.set_pixel(5, 10);
+            machine.run(opcode);
+            assert!(machine.pixel(5, 8), "{:?} {:04X}", mode, opcode);
+            assert_eq!(machine.lit_pixels(), 1, "{:?} {:04X}", mode, opcode);
+
+            let mut machine = Machine::hires(mode);
+            machine.set_pixel(5, 10);
+            machine.run(opcode);
+            assert!(machine.pixel(5, 9), "{:?} {:04X}", mode, opcode);
+            assert_eq!(machine.lit_pixels(), 1, "{:?} {:04X}", mode, opcode);
+
+            let mut machine = Machine::hires(mode);
+            machine.set_pixel(5, 0);
+            machine.run(opcode);
+            assert_eq!(machine.lit_pixels(), 0, "{:?} {:04X}", mode, opcode);
+        }
+    }
+}
+
+#[test]
+fn i00fb_scrolls_right(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_pixel(5, 3);
+        machine.run(0x00FB);
+        assert!(machine.pixel(13, 3), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(5, 3);
+        machine.run(0x00FB);
+        assert!(machine.pixel(9, 3), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(DISPLAY_WIDTH - 1, 3);
+        machine.run(0x00FB);
+        assert_eq!(machine.lit_pixels(), 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i00fc_scrolls_left(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_pixel(20, 3);
+        machine.run(0x00FC);
+        assert!(machine.pixel(12, 3), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(20, 3);
+        machine.run(0x00FC);
+        assert!(machine.pixel(16, 3), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 1, "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_pixel(2, 3);
+        machine.run(0x00FC);
+        assert_eq!(machine.lit_pixels(), 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn scrolls_move_only_the_selected_planes(){
+    for mode in MODES {
+        for opcode in [0x00C1, 0x00B1, 0x00D1, 0x00FB, 0x00FC] {
+            let mut machine = Machine::hires(mode);
+            machine.display.plane_1[10 * DISPLAY_WIDTH + 10] = true;
+            machine.display.plane_2[10 * DISPLAY_WIDTH + 10] = true;
+
+            machine.load(&[0xF201, opcode]);
+            machine.step();
+            machine.step();
+            assert!(machine.pixel(10, 10), "{:?} {:04X}", mode, opcode);
+            assert!(!machine.pixel_2(10, 10), "{:?} {:04X}", mode, opcode);
+        }
+    }
+}
+
+#[test]
+fn i1nnn_jumps(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.run(0x1ABC);
+        assert_eq!(machine.cpu.pc, 0xABC, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i3xnn_and_4xnn_skip_on_value(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[3] = 0x42;
+        machine.load(&[0x3342, NEXT]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x204, "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[3] = 0x42;
+        machine.load(&[0x3343, NEXT]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[3] = 0x42;
+        machine.load(&[0x4343, NEXT]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x204, "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[3] = 0x42;
+        machine.load(&[0x4342, NEXT]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i5xy0_and_9xy0_skip_on_registers(){
+    for mode in MODES {
+        for (a, b, skip_5xy0) in [(7, 7, true), (7, 8, false)] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = a;
+            machine.cpu.registers[2] = b;
+            machine.load(&[0x5120, NEXT]);
+            machine.step();
+            assert_eq!(machine.cpu.pc, if skip_5xy0 { 0x204 } else { 0x202 }, "{:?}", mode);
+
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = a;
+            machine.cpu.registers[2] = b;
+            machine.load(&[0x9120, NEXT]);
+            machine.step();
+            assert_eq!(machine.cpu.pc, if skip_5xy0 { 0x202 } else { 0x204 }, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn skips_step_over_the_whole_f000_instruction(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.load(&[0x3000, 0xF000, 0x1234, NEXT]);
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x206, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i5xy2_saves_a_register_range(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1..=3].copy_from_slice(&[1, 2, 3]);
+        machine.cpu.i = 0x300;
+
+        machine.run(0x5132);
+        assert_eq!(machine.cpu.memory()[0x300..0x304], [1, 2, 3, 0], "{:?}", mode);
+        assert_eq!(machine.cpu.i, 0x300, "{:?}", mode);
+
+        // Reversed ranges are stored in reverse order
+        machine.run(0x5312);
+        assert_eq!(machine.cpu.memory()[0x300..0x304], [3, 2, 1, 0], "{:?}", mode);
+    }
+}
+
+#[test]
+fn i5xy3_loads_a_register_range(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &[1, 2, 3]);
+
+        machine.run(0x5133);
+        assert_eq!(machine.cpu.registers[0..5], [0, 1, 2, 3, 0], "{:?}", mode);
+        assert_eq!(machine.cpu.i, 0x300, "{:?}", mode);
+
+        machine.run(0x5313);
+        assert_eq!(machine.cpu.registers[0..5], [0, 3, 2, 1, 0], "{:?}", mode);
+    }
+}
+
+#[test]
+fn i6xnn_and_7xnn_set_and_add_without_carry(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 5;
+
+        machine.run(0x62FF);
+        assert_eq!(machine.cpu.registers[2], 0xFF, "{:?}", mode);
+
+        machine.run(0x7202);
+        assert_eq!(machine.cpu.registers[2], 0x01, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], 5, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i8xy0_copies(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[2] = 0x99;
+        machine.run(0x8120);
+        assert_eq!(machine.cpu.registers[1], 0x99, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i8xy1_to_8xy3_logic_and_vf_reset(){
+    for mode in MODES {
+        for (opcode, expected) in [(0x8121, 0b1110), (0x8122, 0b1000), (0x8123, 0b0110)] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = 0b1100;
+            machine.cpu.registers[2] = 0b1010;
+            machine.cpu.registers[0xF] = 5;
+
+            machine.run(opcode);
+            assert_eq!(machine.cpu.registers[1], expected, "{:?} {:04X}", mode, opcode);
+
+            let vf = if mode == Mode::Chip8 { 0 } else { 5 };
+            assert_eq!(machine.cpu.registers[0xF], vf, "{:?} {:04X}", mode, opcode);
+        }
+    }
+}
+
+#[test]
+fn i8xy4_adds_with_carry(){
+    for mode in MODES {
+        for (a, b, result, carry) in [(0x10, 0x20, 0x30, 0), (0xF0, 0x20, 0x10, 1), (0xFF, 0x01, 0x00, 1)] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = a;
+            machine.cpu.registers[2] = b;
+            machine.run(0x8124);
+            assert_eq!(machine.cpu.registers[1], result, "{:?} {:02X}+{:02X}", mode, a, b);
+            assert_eq!(machine.cpu.registers[0xF], carry, "{:?} {:02X}+{:02X}", mode, a, b);
+        }
+    }
+}
+
+#[test]
+fn i8xy5_subtracts_with_no_borrow_flag(){
+    for mode in MODES {
+        for (a, b, result, no_borrow) in [(0x30, 0x10, 0x20, 1), (0x10, 0x30, 0xE0, 0), (0x10, 0x10, 0x00, 1)] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = a;
+            machine.cpu.registers[2] = b;
+            machine.run(0x8125);
+            assert_eq!(machine.cpu.registers[1], result, "{:?} {:02X}-{:02X}", mode, a, b);
+            assert_eq!(machine.cpu.registers[0xF], no_borrow, "{:?} {:02X}-{:02X}", mode, a, b);
+        }
+    }
+}
+
+#[test]
+fn i8xy7_subtracts_reversed_with_no_borrow_flag(){
+    for mode in MODES {
+        for (a, b, result, no_borrow) in [(0x10, 0x30, 0x20, 1), (0x30, 0x10, 0xE0, 0), (0x10, 0x10, 0x00, 1)] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = a;
+            machine.cpu.registers[2] = b;
+            machine.run(0x8127);
+            assert_eq!(machine.cpu.registers[1], result, "{:?} {:02X}-{:02X}", mode, b, a);
+            assert_eq!(machine.cpu.registers[0xF], no_borrow, "{:?} {:02X}-{:02X}", mode, b, a);
+        }
+    }
+}
+
+#[test]
+fn i8xy6_shifts_right_with_shift_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 0b0000_0101;
+        machine.cpu.registers[2] = 0b1000_0010;
+        machine.run(0x8126);
+
+        // Super-Chip shifts VX in place, the others shift VY into VX
+        let (result, flag) = if mode == Mode::SuperChip { (0b0000_0010, 1) } else { (0b0100_0001, 0) };
+        assert_eq!(machine.cpu.registers[1], result, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[2], 0b1000_0010, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], flag, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i8xye_shifts_left_with_shift_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 0b0000_0101;
+        machine.cpu.registers[2] = 0b1000_0010;
+        machine.run(0x812E);
+
+        let (result, flag) = if mode == Mode::SuperChip { (0b0000_1010, 0) } else { (0b0000_0100, 1) };
+        assert_eq!(machine.cpu.registers[1], result, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[2], 0b1000_0010, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], flag, "{:?}", mode);
+    }
+}
+
+#[test]
+fn flag_is_written_after_the_result_when_vx_is_vf(){
+    for mode in MODES {
+        // 0xFF + 0x01 = 0x00 with carry
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 0xFF;
+        machine.cpu.registers[1] = 0x01;
+        machine.run(0x8F14);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?} 8XY4", mode);
+
+        // 0x01 - 0x02 borrows
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 0x01;
+        machine.cpu.registers[1] = 0x02;
+        machine.run(0x8F15);
+        assert_eq!(machine.cpu.registers[0xF], 0, "{:?} 8XY5", mode);
+
+        // 0x03 - 0x02 doesn't borrow
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 0x02;
+        machine.cpu.registers[1] = 0x03;
+        machine.run(0x8F17);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?} 8XY7", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 0b0000_0011;
+        machine.cpu.registers[1] = 0b0000_0010;
+        machine.run(0x8F16);
+        let flag = if mode == Mode::SuperChip { 1 } else { 0 };
+        assert_eq!(machine.cpu.registers[0xF], flag, "{:?} 8XY6", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0xF] = 0b1000_0000;
+        machine.cpu.registers[1] = 0b0100_0000;
+        machine.run(0x8F1E);
+        let flag = if mode == Mode::SuperChip { 1 } else { 0 };
+        assert_eq!(machine.cpu.registers[0xF], flag, "{:?} 8XYE", mode);
+    }
+}
+
+#[test]
+fn iannn_sets_i(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.run(0xA123);
+        assert_eq!(machine.cpu.i, 0x123, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ibnnn_jumps_with_offset(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0] = 0x01;
+        machine.cpu.registers[2] = 0x10;
+        machine.run(0xB234);
+
+        // Super-Chip adds VX, the others V0
+        let pc = if mode == Mode::SuperChip { 0x244 } else { 0x235 };
+        assert_eq!(machine.cpu.pc, pc, "{:?}", mode);
+    }
+}
+
+#[test]
+fn icxnn_masks_the_random_value(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 0xFF;
+        machine.run(0xC100);
+        assert_eq!(machine.cpu.registers[1], 0, "{:?}", mode);
+
+        for _ in 0..100 {
+            machine.cpu.pc = 0x200;
+            machine.run(0xC10F);
+            assert!(machine.cpu.registers[1] <= 0x0F, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn icxnn_repeats_with_the_same_seed(){
+    let mut runs = vec![];
+    for _ in 0..2 {
+        let mut machine = Machine::new(Mode::Chip8);
+        machine.cpu.seed_rng(42);
+        let mut values = vec![];
+        for _ in 0..16 {
+            machine.cpu.pc = 0x200;
+            machine.run(0xC1FF);
+            values.push(machine.cpu.registers[1]);
+        }
+        runs.push(values);
+    }
+    assert_eq!(runs[0], runs[1]);
+    assert!(runs[0].iter().any(|&value| value != runs[0][0]));
+}
+
+#[test]
+fn idxyn_doubles_pixels_in_lores(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &[0x80]);
+        machine.run(0xD011);
+
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            assert!(machine.pixel(x, y), "{:?} ({}, {})", mode, x, y);
+        }
+        assert_eq!(machine.lit_pixels(), 4, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_draws_single_pixels_in_hires(){
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0b1010_0000, 0b0100_0000]);
+        machine.cpu.registers[0] = 10;
+        machine.cpu.registers[1] = 20;
+        machine.run(0xD012);
+
+        assert!(machine.pixel(10, 20), "{:?}", mode);
+        assert!(machine.pixel(12, 20), "{:?}", mode);
+        assert!(machine.pixel(11, 21), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 3, "{:?}", mode);
+        assert_eq!(machine.cpu.i, 0x300, "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_xors_and_reports_collisions(){
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0xF0]);
+        machine.cpu.registers[0xF] = 1;
+
+        machine.run(0xD011);
+        assert_eq!(machine.cpu.registers[0xF], 0, "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 4, "{:?}", mode);
+
+        machine.run(0xD011);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 0, "{:?}", mode);
+
+        // Overlapping by one pixel is still a collision
+        machine.run(0xD011);
+        machine.set_memory(0x300, &[0x10]);
+        machine.run(0xD011);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?}", mode);
+        assert!(!machine.pixel(3, 0), "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_wraps_the_start_position(){
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0x80]);
+        machine.cpu.registers[0] = (DISPLAY_WIDTH + 2) as u8;
+        machine.cpu.registers[1] = (DISPLAY_HEIGHT + 3) as u8;
+        machine.run(0xD011);
+        assert!(machine.pixel(2, 3), "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &[0x80]);
+        machine.cpu.registers[0] = 66;
+        machine.cpu.registers[1] = 33;
+        machine.run(0xD011);
+        assert!(machine.pixel(4, 2), "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_clips_or_wraps_at_the_edges(){
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0xFF, 0xFF]);
+        machine.cpu.registers[0] = (DISPLAY_WIDTH - 4) as u8;
+        machine.cpu.registers[1] = (DISPLAY_HEIGHT - 1) as u8;
+        machine.run(0xD012);
+
+        for x in DISPLAY_WIDTH - 4..DISPLAY_WIDTH {
+            assert!(machine.pixel(x, DISPLAY_HEIGHT - 1), "{:?}", mode);
+        }
+
+        // XO-Chip and Experimental wrap sprites around, Chip-8 and Super-Chip clip them
+        let wraps = matches!(mode, Mode::XoChip | Mode::Experimental);
+        assert_eq!(machine.pixel(0, DISPLAY_HEIGHT - 1), wraps, "{:?}", mode);
+        assert_eq!(machine.pixel(DISPLAY_WIDTH - 1, 0), wraps, "{:?}", mode);
+        assert_eq!(machine.pixel(0, 0), wraps, "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), if wraps { 16 } else { 4 }, "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_clipping_follows_the_wrap_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.set_quirk(Quirk::Wrap, true);
+        machine.set_memory(0x300, &[0xFF]);
+        machine.cpu.registers[0] = 60;
+        machine.run(0xD011);
+        assert!(machine.pixel(0, 0), "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.cpu.set_quirk(Quirk::Wrap, false);
+        machine.set_memory(0x300, &[0xFF]);
+        machine.cpu.registers[0] = 60;
+        machine.run(0xD011);
+        assert!(!machine.pixel(0, 0), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 16, "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxyn_draws_to_the_selected_planes(){
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0x80, 0x40]);
+
+        machine.run(0xF201);
+        machine.run(0xD011);
+        assert!(!machine.pixel(0, 0), "{:?}", mode);
+        assert!(machine.pixel_2(0, 0), "{:?}", mode);
+
+        // With both planes selected the sprite data for plane 2 follows the data for plane 1
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0x80, 0x40]);
+        machine.run(0xF301);
+        machine.run(0xD011);
+        assert!(machine.pixel(0, 0), "{:?}", mode);
+        assert!(!machine.pixel(1, 0), "{:?}", mode);
+        assert!(machine.pixel_2(1, 0), "{:?}", mode);
+        assert!(!machine.pixel_2(0, 0), "{:?}", mode);
+
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &[0x80]);
+        machine.run(0xF001);
+        machine.run(0xD011);
+        assert_eq!(machine.display, Display{ selected_plane: 0, ..Display::new() }, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn idxy0_draws_16x16_sprites(){
+    let mut sprite = [0u8; 32];
+    sprite[0] = 0xFF;
+    sprite[1] = 0xFF;
+    sprite[2] = 0x80;
+    sprite[3] = 0x01;
+
+    for mode in MODES {
+        let mut machine = Machine::hires(mode);
+        machine.set_memory(0x300, &sprite);
+        machine.run(0xD010);
+
+        for x in 0..16 {
+            assert!(machine.pixel(x, 0), "{:?} ({}, 0)", mode, x);
+        }
+        assert!(machine.pixel(0, 1), "{:?}", mode);
+        assert!(machine.pixel(15, 1), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 18, "{:?}", mode);
+
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &sprite);
+        machine.run(0xD010);
+        assert!(machine.pixel(31, 1), "{:?}", mode);
+        assert!(!machine.pixel(32, 0), "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 18 * 4, "{:?}", mode);
+
+        machine.run(0xD010);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?}", mode);
+        assert_eq!(machine.lit_pixels(), 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn iex9e_and_exa1_skip_on_keys(){
+    for mode in MODES {
+        for pressed in [true, false] {
+            let mut machine = Machine::new(mode);
+            // Only the low nibble picks the key
+            machine.cpu.registers[1] = 0x15;
+            machine.keys[5] = pressed;
+            machine.load(&[0xE19E, NEXT]);
+            machine.step();
+            assert_eq!(machine.cpu.pc, if pressed { 0x204 } else { 0x202 }, "{:?} pressed: {}", mode, pressed);
+
+            let mut machine = Machine::new(mode);
+            machine.cpu.registers[1] = 0x15;
+            machine.keys[5] = pressed;
+            machine.load(&[0xE1A1, NEXT]);
+            machine.step();
+            assert_eq!(machine.cpu.pc, if pressed { 0x202 } else { 0x204 }, "{:?} pressed: {}", mode, pressed);
+        }
+    }
+}
+
+#[test]
+fn if000_loads_a_16_bit_address(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.load(&[0xF000, 0xABCD]);
+        machine.step();
+        assert_eq!(machine.cpu.i, 0xABCD, "{:?}", mode);
+        assert_eq!(machine.cpu.pc, 0x204, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifn01_selects_planes(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        for n in 0..4 {
+            machine.run(0xF001 | (n << 8));
+            assert_eq!(machine.display.selected_plane, n as u8, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn if002_loads_the_audio_pattern(){
+    let pattern: Vec<u8> = (1..=16).collect();
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &pattern);
+        machine.run(0xF002);
+        assert_eq!(machine.cpu.sound_pattern_buffer[..], pattern[..], "{:?}", mode);
+        assert_eq!(machine.cpu.i, 0x300, "{:?}", mode);
+    }
+}
+
+#[test]
+fn timers_are_read_and_written(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 30;
+        machine.cpu.registers[2] = 40;
+
+        machine.run(0xF115);
+        machine.run(0xF218);
+        assert_eq!(machine.cpu.delay_timer, 30, "{:?}", mode);
+        assert_eq!(machine.cpu.sound_timer, 40, "{:?}", mode);
+
+        machine.run(0xF307);
+        assert_eq!(machine.cpu.registers[3], 30, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx0a_waits_for_a_key_release(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.load(&[0xF30A]);
+
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x200, "{:?} no key", mode);
+        assert_eq!(machine.cpu.awaiting_key, None, "{:?}", mode);
+
+        // The lowest pressed key is picked, but nothing happens until it is released
+        machine.keys[7] = true;
+        machine.keys[3] = true;
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x200, "{:?} pressed", mode);
+        assert_eq!(machine.cpu.awaiting_key, Some(3), "{:?}", mode);
+
+        machine.keys[7] = false;
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x200, "{:?} still held", mode);
+        assert_eq!(machine.cpu.registers[3], 0, "{:?}", mode);
+
+        machine.keys[3] = false;
+        machine.step();
+        assert_eq!(machine.cpu.pc, 0x202, "{:?} released", mode);
+        assert_eq!(machine.cpu.registers[3], 3, "{:?}", mode);
+        assert_eq!(machine.cpu.awaiting_key, None, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx1e_adds_to_i_with_overflow_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.i = 0xFFFF;
+        machine.cpu.registers[1] = 2;
+        machine.cpu.registers[0xF] = 5;
+        machine.run(0xF11E);
+        assert_eq!(machine.cpu.i, 0x0001, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], 5, "{:?}", mode);
+
+        machine.cpu.set_quirk(Quirk::IOverflow, true);
+        machine.cpu.i = 0xFFFF;
+        machine.run(0xF11E);
+        assert_eq!(machine.cpu.registers[0xF], 1, "{:?}", mode);
+
+        machine.run(0xF11E);
+        assert_eq!(machine.cpu.i, 0x0003, "{:?}", mode);
+        assert_eq!(machine.cpu.registers[0xF], 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx29_and_fx30_point_to_font_characters(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 0xA;
+        machine.run(0xF129);
+        assert_eq!(machine.cpu.i as usize, FONT_MEMORY_START + 0xA * 5, "{:?}", mode);
+
+        // The big font is loaded right after the small one
+        machine.cpu.registers[1] = 3;
+        machine.run(0xF130);
+        assert_eq!(machine.cpu.i as usize, FONT_MEMORY_START + FONT_DATA.len() + 3 * 10, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx33_stores_bcd(){
+    for mode in MODES {
+        for (value, digits) in [(254, [2, 5, 4]), (7, [0, 0, 7]), (40, [0, 4, 0])] {
+            let mut machine = Machine::new(mode);
+            machine.cpu.i = 0x300;
+            machine.cpu.registers[1] = value;
+            machine.run(0xF133);
+            assert_eq!(machine.cpu.memory()[0x300..0x303], digits, "{:?} {}", mode, value);
+            assert_eq!(machine.cpu.i, 0x300, "{:?}", mode);
+        }
+    }
+}
+
+#[test]
+fn ifx3a_sets_the_pitch(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[1] = 112;
+        machine.run(0xF13A);
+        assert_eq!(machine.cpu.pitch_register, 112, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx55_stores_registers_with_memory_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        machine.cpu.i = 0x300;
+        machine.run(0xF255);
+
+        assert_eq!(machine.cpu.memory()[0x300..0x304], [1, 2, 3, 0], "{:?}", mode);
+
+        // Chip-8 and XO-Chip leave I after the last stored register
+        let i = if matches!(mode, Mode::Chip8 | Mode::XoChip) { 0x303 } else { 0x300 };
+        assert_eq!(machine.cpu.i, i, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx65_loads_registers_with_memory_quirk(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.set_memory(0x300, &[1, 2, 3, 4]);
+        machine.run(0xF265);
+
+        assert_eq!(machine.cpu.registers[0..4], [1, 2, 3, 0], "{:?}", mode);
+
+        let i = if matches!(mode, Mode::Chip8 | Mode::XoChip) { 0x303 } else { 0x300 };
+        assert_eq!(machine.cpu.i, i, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx75_and_fx85_save_and_restore_flags(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0..5].copy_from_slice(&[1, 2, 3, 4, 5]);
+        machine.run(0xF375);
+        assert_eq!(machine.cpu.rpl_flags[0..8], [1, 2, 3, 4, 0, 0, 0, 0], "{:?}", mode);
+
+        machine.cpu.registers = [0; 16];
+        machine.run(0xF285);
+        assert_eq!(machine.cpu.registers[0..5], [1, 2, 3, 0, 0], "{:?}", mode);
+    }
+}
+
+#[test]
+fn fetch_wraps_at_the_end_of_memory(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.pc = MEMORY_SIZE - 1;
+        machine.cpu.write_byte(MEMORY_SIZE - 1, 0x61);
+        machine.cpu.write_byte(0, 0x23);
+        machine.step();
+        assert_eq!(machine.cpu.registers[1], 0x23, "{:?}", mode);
+        assert_eq!(machine.cpu.pc, 1, "{:?}", mode);
+    }
+}
+
+#[test]
+fn memory_access_wraps_at_the_end_of_memory(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers[0..3].copy_from_slice(&[1, 2, 3]);
+        machine.cpu.i = 0xFFFF;
+        machine.run(0xF255);
+        assert_eq!(machine.cpu.memory()[0xFFFF], 1, "{:?}", mode);
+        assert_eq!(machine.cpu.memory()[0..2], [2, 3], "{:?}", mode);
+
+        // The memory quirk wraps I as well
+        let i = if matches!(mode, Mode::Chip8 | Mode::XoChip) { 0x0002 } else { 0xFFFF };
+        assert_eq!(machine.cpu.i, i, "{:?}", mode);
+
+        machine.cpu.i = 0xFFFE;
+        machine.cpu.registers[1] = 123;
+        machine.run(0xF133);
+        assert_eq!(machine.cpu.memory()[0xFFFE..], [1, 2], "{:?}", mode);
+        assert_eq!(machine.cpu.memory()[0], 3, "{:?}", mode);
+    }
+}
+
+// Instructions are cached after decoding, writes to them must be seen the next time they run
+#[test]
+fn self_modifying_code_is_decoded_again(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.run(0x6207);
+        assert_eq!(machine.cpu.registers[2], 0x07, "{:?}", mode);
+
+        // FX55 rewrites only the low byte of the cached instruction
+        machine.cpu.registers[0] = 0x09;
+        machine.cpu.i = 0x201;
+        machine.run(0xF055);
+        machine.cpu.pc = 0x200;
+        machine.step();
+        assert_eq!(machine.cpu.registers[2], 0x09, "{:?}", mode);
+
+        // 5XY2 replaces it with 6105
+        machine.cpu.registers[4..6].copy_from_slice(&[0x61, 0x05]);
+        machine.cpu.i = 0x200;
+        machine.run(0x5452);
+        machine.cpu.pc = 0x200;
+        machine.step();
+        assert_eq!(machine.cpu.registers[1], 0x05, "{:?}", mode);
+
+        // FX33 writes 1, 6 and 2 from 0x1FF, 0602 isn't an instruction so nothing runs
+        machine.cpu.registers[3] = 162;
+        machine.cpu.i = 0x1FF;
+        machine.run(0xF333);
+        machine.cpu.registers[1] = 0;
+        machine.cpu.pc = 0x200;
+        machine.step();
+        assert_eq!(machine.cpu.registers[1], 0, "{:?}", mode);
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx0a_waits_at_the_start_of_memory(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.pc = 0;
+        machine.run(0xF10A);
+        assert_eq!(machine.cpu.pc, 0, "{:?}", mode);
+    }
+}
+
+#[test]
+fn i2nnn_ignores_calls_past_the_stack_limit(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.stack = vec![0x300; STACK_SIZE];
+        machine.run(0x2400);
+        assert_eq!(machine.cpu.stack.len(), STACK_SIZE, "{:?}", mode);
+        assert_eq!(machine.cpu.pc, 0x202, "{:?}", mode);
+    }
+}
+
+#[test]
+fn ifx75_and_fx85_use_all_16_flags(){
+    for mode in MODES {
+        let mut machine = Machine::new(mode);
+        machine.cpu.registers = [7; 16];
+        machine.run(0xFF75);
+        assert_eq!(machine.cpu.rpl_flags, [7; 16], "{:?}", mode);
+
+        machine.cpu.registers = [0; 16];
+        machine.run(0xFF85);
+        assert_eq!(machine.cpu.registers, [7; 16], "{:?}", mode);
+    }
+}
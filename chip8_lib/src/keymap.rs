@@ -0,0 +1,114 @@
+use crate::keypad::KeyPad;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Keys are frontend neutral, lowercase names: letters and symbols ("q", "1", "é"),
+// numeric keypad keys prefixed with "kp" ("kp7", "kp/", "kpenter").
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(tag = "layout", rename_all = "snake_case"))]
+pub enum Layout{
+    Qwerty,
+    Qwertz,
+    Azerty,
+    NumericKeypad,
+    Custom{ keys: Box<[String; 16]> }, // indexed by KeyPad value, 0 to F
+}
+
+// How a frontend names the keys of the number row: browsers give the typed character ("&" on the
+// AZERTY 1 key), SDL gives its key code, which is the digit on every layout
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+pub enum KeyNames{
+    #[default]
+    Characters,
+    KeyCodes,
+}
+
+impl Layout{
+    pub fn presets() -> [Layout; 4]{
+        [Layout::Qwerty, Layout::Qwertz, Layout::Azerty, Layout::NumericKeypad]
+    }
+
+    pub fn name(&self) -> &'static str{
+        match self {
+            Layout::Qwerty => "QWERTY",
+            Layout::Qwertz => "QWERTZ",
+            Layout::Azerty => "AZERTY",
+            Layout::NumericKeypad => "Numeric keypad",
+            Layout::Custom{..} => "Custom",
+        }
+    }
+
+    fn keys(&self, names: KeyNames) -> [&str; 16]{
+        match self {
+            // Keys ordered by KeyPad value: 0 1 2 3 4 5 6 7 8 9 A B C D E F
+            Layout::Qwerty => ["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", "v"],
+            Layout::Qwertz => ["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "y", "c", "4", "r", "f", "v"],
+            Layout::Azerty if names == KeyNames::KeyCodes => ["x", "1", "2", "3", "a", "z", "e", "q", "s", "d", "w", "c", "4", "r", "f", "v"],
+            Layout::Azerty => ["x", "&", "é", "\"", "a", "z", "e", "q", "s", "d", "w", "c", "'", "r", "f", "v"],
+            Layout::NumericKeypad => ["kp.", "kp7", "kp8", "kp9", "kp4", "kp5", "kp6", "kp1", "kp2", "kp3", "kp0", "kpenter", "kp/", "kp*", "kp-", "kp+"],
+            Layout::Custom{keys} => std::array::from_fn(|i| keys[i].as_str()),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(from = "Layout", into = "Layout"))]
+pub struct KeyMap{
+    layout: Layout,
+    keys: [String; 16],
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        KeyMap::new(Layout::Qwerty)
+    }
+}
+
+impl From<Layout> for KeyMap {
+    fn from(layout: Layout) -> KeyMap {
+        KeyMap::new(layout)
+    }
+}
+
+impl From<KeyMap> for Layout {
+    fn from(key_map: KeyMap) -> Layout {
+        key_map.layout
+    }
+}
+
+impl KeyMap{
+    // With the key names of the browser
+    pub fn new(layout: Layout) -> KeyMap{
+        KeyMap::with_key_names(layout, KeyNames::Characters)
+    }
+
+    pub fn with_key_names(layout: Layout, names: KeyNames) -> KeyMap{
+        let keys = layout.keys(names).map(|key| key.to_lowercase());
+        KeyMap{ layout, keys }
+    }
+
+    pub fn custom(keys: [String; 16]) -> KeyMap{
+        KeyMap::new(Layout::Custom{ keys: Box::new(keys) })
+    }
+
+    pub fn layout(&self) -> &Layout{
+        &self.layout
+    }
+
+    pub fn get_keypad(&self, key: &str) -> Option<KeyPad>{
+        let key = key.to_lowercase();
+        self.keys.iter().position(|mapped| *mapped == key).and_then(KeyPad::from_index)
+    }
+
+    pub fn get_key(&self, keypad: KeyPad) -> &str{
+        &self.keys[keypad as usize]
+    }
+
+    pub fn get_key_label(&self, keypad: KeyPad) -> String{
+        let key = self.get_key(keypad);
+        match key.strip_prefix("kp") {
+            Some(rest) if !rest.is_empty() => format!("Num {}", rest.to_uppercase()),
+            _ => key.to_uppercase(),
+        }
+    }
+}
@@ -1,5 +1,5 @@
 #[repr(usize)]
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum KeyPad{
     Num0 = 0,
     Num1 = 1,
@@ -24,6 +24,15 @@ impl KeyPad{
         [ KeyPad::Num1, KeyPad::Num2, KeyPad::Num3, KeyPad::C, KeyPad::Num4, KeyPad::Num5, KeyPad::Num6, KeyPad::D, KeyPad::Num7, KeyPad::Num8, KeyPad::Num9, KeyPad::E, KeyPad::A, KeyPad::Num0, KeyPad::B, KeyPad::F]
     }
 
+    pub fn from_index(index: usize) -> Option<KeyPad>{
+        KeyPad::all().into_iter().find(|key| *key as usize == index)
+    }
+
+    // Accepts the hex digit of the key, "0" to "F"
+    pub fn from_chip8_str(key: &str) -> Option<KeyPad>{
+        KeyPad::all().into_iter().find(|keypad| keypad.to_chip8_str().eq_ignore_ascii_case(key.trim()))
+    }
+
     pub fn to_chip8_str(&self) -> &'static str{
         match self{
             KeyPad::Num0 => "0",
@@ -44,25 +53,9 @@ impl KeyPad{
             KeyPad::F => "F",
         }
     }
+}
 
-    pub fn to_keyboard_str(&self) -> &'static str{
-        match self{
-            KeyPad::Num0 => "X",
-            KeyPad::Num1 => "1",
-            KeyPad::Num2 => "2",
-            KeyPad::Num3 => "3",
-            KeyPad::Num4 => "Q",
-            KeyPad::Num5 => "W",
-            KeyPad::Num6 => "E",
-            KeyPad::Num7 => "A",
-            KeyPad::Num8 => "S",
-            KeyPad::Num9 => "D",
-            KeyPad::A => "Z",
-            KeyPad::B => "C",
-            KeyPad::C => "4",
-            KeyPad::D => "R",
-            KeyPad::E => "F",
-            KeyPad::F => "V",
-        }
-    }
+// Bit N of the mask is set when key N is pressed
+pub fn keys_from_mask(mask: u16) -> [bool; 16]{
+    std::array::from_fn(|i| (mask >> i) & 1 == 1)
 }
@@ -1,7 +1,18 @@
+pub mod args;
 pub mod chip_8;
 pub mod cpu_state;
 pub mod decoded_instruction;
 pub mod display;
+pub mod frame_composer;
 pub mod instructions;
 pub mod parameters;
 pub mod keypad;
+pub mod keymap;
+#[cfg(any(test, feature = "test-support"))]
+pub mod machine;
+pub mod palette;
+pub mod screenshot;
+pub mod sound;
+pub mod trace;
+pub mod triple_buffer;
+pub mod wav;
@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::chip_8::Mode;
+use crate::cpu_state::CpuState;
+use crate::display::Display;
+use crate::instructions::Instruction;
+use crate::parameters::*;
+
+// One CPU with its display, keys and flags, stepped the same way the execution thread does
+// but without locking or timers. For tests and benchmarks.
+pub struct Machine{
+    pub cpu: CpuState,
+    pub display: Display,
+    pub keys: [bool; 16],
+    pub hires_mode: AtomicBool,
+    pub running: AtomicBool,
+}
+
+impl Machine{
+    pub fn new(mode: Mode) -> Machine{
+        let mut cpu = CpuState::default();
+        cpu.set_compatibility_mode(&mode);
+
+        Machine{
+            cpu,
+            display: Display::new(),
+            keys: [false; 16],
+            hires_mode: AtomicBool::new(false),
+            running: AtomicBool::new(true),
+        }
+    }
+
+    pub fn hires(mode: Mode) -> Machine{
+        let machine = Machine::new(mode);
+        machine.hires_mode.store(true, Ordering::Relaxed);
+        machine
+    }
+
+    // Writes the opcodes starting at pc
+    pub fn load(&mut self, opcodes: &[u16]){
+        for (idx, opcode) in opcodes.iter().enumerate() {
+            let address = self.cpu.pc + idx * 2;
+            self.cpu.load_memory(address, &opcode.to_be_bytes());
+        }
+    }
+
+    pub fn step(&mut self){
+        if let Some(instruction) = self.cpu.get_current_instruction(true) {
+            self.execute(instruction);
+        }
+    }
+
+    // Loads the opcode at pc and runs it
+    pub fn run(&mut self, opcode: u16){
+        self.load(&[opcode]);
+        self.step();
+    }
+
+    pub fn steps(&mut self, count: u64){
+        for _ in 0..count {
+            self.step();
+        }
+    }
+
+    pub fn execute(&mut self, instruction: Instruction){
+        instruction.execute(&mut self.cpu, &mut self.display, &self.keys, &self.hires_mode, &self.running);
+    }
+
+    pub fn set_memory(&mut self, address: u16, bytes: &[u8]){
+        self.cpu.i = address;
+        self.cpu.load_memory(address as usize, bytes);
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool{
+        self.display.plane_1[y * DISPLAY_WIDTH + x]
+    }
+
+    pub fn pixel_2(&self, x: usize, y: usize) -> bool{
+        self.display.plane_2[y * DISPLAY_WIDTH + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize){
+        self.display.plane_1[y * DISPLAY_WIDTH + x] = true;
+    }
+
+    pub fn lit_pixels(&self) -> usize{
+        self.display.plane_1.iter().filter(|&&on| on).count()
+    }
+}
@@ -0,0 +1,100 @@
+use crate::display::Display;
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Palette{
+    pub background: Rgb,
+    pub plane_1: Rgb,
+    pub plane_2: Rgb,
+    pub mixed: Rgb,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::sky()
+    }
+}
+
+impl Palette{
+    pub fn sky() -> Palette{
+        Palette{
+            background: (135, 206, 235),
+            plane_1: (85, 68, 34),
+            plane_2: (69, 101, 67),
+            mixed: (238, 238, 255),
+        }
+    }
+
+    pub fn mono() -> Palette{
+        Palette{
+            background: (0, 0, 0),
+            plane_1: (255, 255, 255),
+            plane_2: (170, 170, 170),
+            mixed: (85, 85, 85),
+        }
+    }
+
+    pub fn amber() -> Palette{
+        Palette{
+            background: (26, 16, 0),
+            plane_1: (255, 176, 0),
+            plane_2: (153, 96, 0),
+            mixed: (255, 226, 153),
+        }
+    }
+
+    pub fn lcd() -> Palette{
+        Palette{
+            background: (155, 188, 15),
+            plane_1: (15, 56, 15),
+            plane_2: (48, 98, 48),
+            mixed: (139, 172, 15),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette>{
+        match name.to_lowercase().as_str() {
+            "sky" => Some(Palette::sky()),
+            "mono" => Some(Palette::mono()),
+            "amber" => Some(Palette::amber()),
+            "lcd" => Some(Palette::lcd()),
+            _ => None
+        }
+    }
+
+    // Colors in order: background, plane 1, plane 2, mixed (e.g. "#87CEEB" or "87CEEB")
+    pub fn from_hex(colors: &[&str]) -> Option<Palette>{
+        if colors.len() != 4 {
+            return None;
+        }
+
+        Some(Palette{
+            background: parse_hex_color(colors[0])?,
+            plane_1: parse_hex_color(colors[1])?,
+            plane_2: parse_hex_color(colors[2])?,
+            mixed: parse_hex_color(colors[3])?,
+        })
+    }
+
+    pub fn get_pixel_color(&self, display: &Display, idx: usize) -> Rgb{
+        match (display.plane_1[idx], display.plane_2[idx]) {
+            (true, true) => self.mixed,
+            (false, true) => self.plane_2,
+            (true, false) => self.plane_1,
+            (false, false) => self.background,
+        }
+    }
+}
+
+pub fn parse_hex_color(color: &str) -> Option<Rgb>{
+    let hex = color.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some((r, g, b))
+}
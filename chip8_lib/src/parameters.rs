@@ -41,3 +41,7 @@ pub const BIG_FONT_DATA: [u8; 160] = [
 ];
 
 pub const FONT_MEMORY_START: usize = 0x50;
+pub const MEMORY_SIZE: usize = 65_536;
+pub const PROGRAM_START: usize = 0x200;
+// 16 levels like the Super-Chip, deeper calls are ignored
+pub const STACK_SIZE: usize = 16;
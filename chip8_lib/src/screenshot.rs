@@ -0,0 +1,53 @@
+use crate::display::Display;
+use crate::palette::Palette;
+use crate::parameters::*;
+
+pub const RGBA_BYTES_PER_PIXEL: usize = 4;
+
+// The display rendered with a palette into an RGBA image. Native size is 64x32 in lores
+// and 128x64 in hires, every native pixel becomes a scale x scale block.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Screenshot{
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Screenshot{
+    pub fn capture(display: &Display, palette: &Palette, hires: bool, scale: u32) -> Screenshot{
+        // Lores pixels are stored doubled in the display
+        let step = if hires { 1 } else { 2 };
+        let scale = scale.max(1) as usize;
+        let width = DISPLAY_WIDTH / step * scale;
+        let height = DISPLAY_HEIGHT / step * scale;
+
+        let mut rgba = Vec::with_capacity(width * height * RGBA_BYTES_PER_PIXEL);
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y / scale * step) * DISPLAY_WIDTH + (x / scale * step);
+                let (r, g, b) = palette.get_pixel_color(display, idx);
+                rgba.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+
+        Screenshot{
+            width: width as u32,
+            height: height as u32,
+            rgba,
+        }
+    }
+
+    #[cfg(feature = "png")]
+    pub fn to_png(&self) -> Result<Vec<u8>, String>{
+        let mut bytes = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+            writer.write_image_data(&self.rgba).map_err(|e| e.to_string())?;
+        }
+        Ok(bytes)
+    }
+}
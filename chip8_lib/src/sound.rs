@@ -0,0 +1,289 @@
+use std::collections::VecDeque;
+use crate::chip_8::Mode;
+use crate::cpu_state::CpuState;
+
+// Emulated time of one 60 Hz timer tick
+pub const TICK_NS: u64 = 16_666_667;
+// Events nobody takes are dropped after about 10 seconds
+const MAX_SOUND_EVENTS: usize = 600;
+pub const BUZZER_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.2;
+// Amplitude at full volume
+pub const MAX_AMPLITUDE: f32 = 0.25;
+const PATTERN_BITS: f32 = 128.0;
+const OVERSAMPLING: usize = 8;
+// Low-pass cutoff as a fraction of the output sample rate, just below Nyquist
+const LOWPASS_CUTOFF: f32 = 0.45;
+// Sounds fade in and out over this time instead of starting and stopping abruptly
+const RAMP_SECONDS: f32 = 0.004;
+// Events are played this far behind the emulation, enough to cover a frame of jitter
+const LATENCY_NS: f64 = 50_000_000.0;
+// The playback clock jumps to the emulated time when it drifts further than this
+const RESYNC_NS: f64 = 150_000_000.0;
+
+// The parts of CpuState that decide what is heard
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct SoundRegisters{
+    pub mode: Mode,
+    pub sound_timer: u8,
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+}
+
+impl SoundRegisters{
+    pub fn from_state(state: &CpuState, mode: Mode) -> SoundRegisters{
+        SoundRegisters{
+            mode,
+            sound_timer: state.sound_timer,
+            pattern: state.sound_pattern_buffer,
+            pitch: state.pitch_register,
+        }
+    }
+
+    pub fn silent(mode: Mode) -> SoundRegisters{
+        SoundRegisters{
+            mode,
+            sound_timer: 0,
+            pattern: [0; 16],
+            pitch: 64,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool{
+        self.sound_timer > 0
+    }
+}
+
+// The sound registers at a point in emulated time, counted from the start of the machine
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct SoundEvent{
+    pub time_ns: u64,
+    pub registers: SoundRegisters,
+}
+
+// Written by the execution thread: the registers at every timer tick and every change in between.
+// Frontends take them each frame and play them back with the same spacing.
+#[derive(Default, Debug)]
+pub struct SoundEvents{
+    events: VecDeque<SoundEvent>,
+    last: Option<SoundRegisters>,
+}
+
+impl SoundEvents{
+    pub fn push(&mut self, time_ns: u64, registers: SoundRegisters){
+        if self.events.len() >= MAX_SOUND_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(SoundEvent{ time_ns, registers });
+        self.last = Some(registers);
+    }
+
+    pub fn push_if_changed(&mut self, time_ns: u64, registers: SoundRegisters){
+        if self.last != Some(registers) {
+            self.push(time_ns, registers);
+        }
+    }
+
+    // Moves the events of other to the end of these
+    pub fn append(&mut self, other: &mut SoundEvents){
+        for event in other.take() {
+            self.push(event.time_ns, event.registers);
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<SoundEvent>{
+        self.events.drain(..).collect()
+    }
+}
+
+// Turns the sound registers into mono PCM samples.
+// XO-Chip plays the 128 bit pattern buffer at 4000 * 2^((pitch - 64) / 48) bits per second,
+// the other modes a 440 Hz square wave. The phase carries over between calls,
+// so audio generated frame by frame has no discontinuities.
+//
+// The square waves are rendered at OVERSAMPLING times the sample rate through a two pole
+// low-pass and averaged down, which removes most of the aliasing at high pitches.
+pub struct SoundGenerator{
+    pub volume: f32, // master volume, 0.0 to 1.0
+    pub muted: bool,
+    sample_rate: u32,
+    registers: SoundRegisters,
+    phase: f32,
+    gain: f32,
+    lowpass: [f32; 2],
+    lowpass_coefficient: f32,
+    ramp_step: f32,
+}
+
+impl SoundGenerator{
+    pub fn new(sample_rate: u32, mode: Mode) -> SoundGenerator{
+        let oversampled_rate = sample_rate as f32 * OVERSAMPLING as f32;
+        let cutoff = sample_rate as f32 * LOWPASS_CUTOFF;
+
+        SoundGenerator{
+            volume: DEFAULT_VOLUME,
+            muted: false,
+            sample_rate,
+            registers: SoundRegisters::silent(mode),
+            phase: 0.0,
+            gain: 0.0,
+            lowpass: [0.0; 2],
+            lowpass_coefficient: 1.0 - (-2.0 * std::f32::consts::PI * cutoff / oversampled_rate).exp(),
+            ramp_step: 1.0 / (RAMP_SECONDS * sample_rate as f32).max(1.0),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32{
+        self.sample_rate
+    }
+
+    pub fn registers(&self) -> SoundRegisters{
+        self.registers
+    }
+
+    pub fn set_registers(&mut self, registers: SoundRegisters){
+        // A new XO-Chip sound starts from the beginning of the pattern, unless the last one is still fading out
+        if registers.is_playing() && !self.registers.is_playing() && registers.mode == Mode::XoChip && self.gain == 0.0 {
+            self.phase = 0.0;
+        }
+        self.registers = registers;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]){
+        let phase_inc = self.get_phase_inc() / OVERSAMPLING as f32;
+        let target_gain = if self.registers.is_playing() && !self.muted { 1.0 } else { 0.0 };
+        let amplitude = self.volume.clamp(0.0, 1.0) * MAX_AMPLITUDE;
+
+        for sample in out.iter_mut() {
+            if self.gain == 0.0 && target_gain == 0.0 {
+                *sample = 0.0;
+                continue;
+            }
+
+            let mut sum = 0.0;
+            for _ in 0..OVERSAMPLING {
+                let raw = if self.get_bit() { 1.0 } else { -1.0 };
+                self.lowpass[0] += self.lowpass_coefficient * (raw - self.lowpass[0]);
+                self.lowpass[1] += self.lowpass_coefficient * (self.lowpass[0] - self.lowpass[1]);
+                sum += self.lowpass[1];
+                self.phase = (self.phase + phase_inc) % 1.0;
+            }
+
+            self.gain = if self.gain < target_gain {
+                (self.gain + self.ramp_step).min(target_gain)
+            } else {
+                (self.gain - self.ramp_step).max(target_gain)
+            };
+
+            *sample = sum / OVERSAMPLING as f32 * self.gain * amplitude;
+        }
+    }
+
+    fn get_phase_inc(&self) -> f32{
+        let frequency = match self.registers.mode {
+            Mode::XoChip => {
+                let playback_rate = 4000.0 * 2f32.powf((self.registers.pitch as f32 - 64.0) / 48.0);
+                playback_rate / PATTERN_BITS
+            },
+            _ => BUZZER_FREQUENCY,
+        };
+        frequency / self.sample_rate as f32
+    }
+
+    fn get_bit(&self) -> bool{
+        match self.registers.mode {
+            Mode::XoChip => {
+                let index = (self.phase * PATTERN_BITS) as usize % 128;
+                let byte = self.registers.pattern[index / 8];
+                (byte >> (7 - (index % 8))) & 1 == 1
+            },
+            _ => self.phase <= 0.5,
+        }
+    }
+}
+
+// Plays sound events at the sample matching their emulated time, so a beep of N timer ticks
+// lasts exactly N/60 s whatever the frame rate of the window. With a speed multiplier
+// the playback clock runs at the same speed as the machine.
+pub struct SoundPlayer{
+    pub generator: SoundGenerator,
+    speed: f64,
+    events: VecDeque<SoundEvent>,
+    clock_ns: f64,
+    synced: bool,
+}
+
+impl SoundPlayer{
+    pub fn new(generator: SoundGenerator) -> SoundPlayer{
+        SoundPlayer{
+            generator,
+            speed: 1.0,
+            events: VecDeque::new(),
+            clock_ns: 0.0,
+            synced: false,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        if speed as f64 != self.speed {
+            self.speed = speed as f64;
+            self.synced = false;
+        }
+    }
+
+    pub fn queue(&mut self, events: Vec<SoundEvent>) {
+        for event in events {
+            let target_ns = event.time_ns as f64 - LATENCY_NS * self.speed;
+            if !self.synced || (target_ns - self.clock_ns).abs() > RESYNC_NS * self.speed {
+                self.resync(target_ns);
+            }
+            self.events.push_back(event);
+        }
+    }
+
+    // Called when the machine restarts and its emulated time starts over
+    pub fn reset(&mut self) {
+        let mode = self.generator.registers().mode;
+        self.generator.set_registers(SoundRegisters::silent(mode));
+        self.events.clear();
+        self.synced = false;
+    }
+
+    // Events still waiting are applied at once
+    fn resync(&mut self, clock_ns: f64) {
+        if let Some(event) = self.events.back().copied() {
+            self.generator.set_registers(event.registers);
+        }
+        self.events.clear();
+        self.clock_ns = clock_ns;
+        self.synced = true;
+    }
+
+    fn get_ns_per_sample(&self) -> f64 {
+        1_000_000_000.0 / self.generator.sample_rate() as f64
+    }
+
+    // Plays the queued events at their time, the clock advances by the length of out
+    pub fn fill(&mut self, out: &mut [f32]){
+        let ns_per_sample = self.get_ns_per_sample();
+        let mut position = 0;
+
+        while position < out.len() {
+            while let Some(event) = self.events.front() && event.time_ns as f64 <= self.clock_ns {
+                self.generator.set_registers(event.registers);
+                self.events.pop_front();
+            }
+
+            // Generate up to the next event
+            let remaining = out.len() - position;
+            let count = match self.events.front() {
+                Some(event) => (((event.time_ns as f64 - self.clock_ns) / (ns_per_sample * self.speed)).ceil() as usize).clamp(1, remaining),
+                None => remaining,
+            };
+
+            self.generator.fill(&mut out[position..position + count]);
+            position += count;
+            self.clock_ns += count as f64 * ns_per_sample * self.speed;
+        }
+    }
+}
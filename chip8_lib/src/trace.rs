@@ -0,0 +1,153 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::cpu_state::CpuState;
+use crate::parameters::*;
+
+const FNV_OFFSET: u32 = 0x811C_9DC5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+// The state before one instruction runs, written one per line:
+// PC:0200 OP:00E0 I:0000 V:000102030405060708090A0B0C0D0E0F SP:0 MEM:811C9DC5
+// All values are hex, MEM is the 32 bit FNV-1a hash of all 64 KiB of memory.
+// Other emulators can write the same format to find the first instruction where they disagree.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct TraceEntry{
+    pub pc: u16,
+    pub opcode: u16,
+    pub i: u16,
+    pub registers: [u8; 16],
+    pub stack_depth: u8,
+    pub memory_hash: u32,
+}
+
+impl TraceEntry{
+    pub fn capture(cpu: &CpuState, memory_hash: u32) -> TraceEntry{
+        TraceEntry{
+            pc: (cpu.pc % MEMORY_SIZE) as u16,
+            opcode: ((cpu.read_byte(cpu.pc) as u16) << 8) | cpu.read_byte(cpu.pc + 1) as u16,
+            i: cpu.i,
+            registers: cpu.registers,
+            stack_depth: cpu.stack.len() as u8,
+            memory_hash,
+        }
+    }
+
+    // Names of the fields that differ, e.g. ["V3", "VF"]
+    pub fn diff(&self, other: &TraceEntry) -> Vec<String>{
+        let mut fields = vec![];
+        if self.pc != other.pc { fields.push("PC".to_string()); }
+        if self.opcode != other.opcode { fields.push("OP".to_string()); }
+        if self.i != other.i { fields.push("I".to_string()); }
+        for (idx, (a, b)) in self.registers.iter().zip(other.registers.iter()).enumerate() {
+            if a != b { fields.push(format!("V{:X}", idx)); }
+        }
+        if self.stack_depth != other.stack_depth { fields.push("SP".to_string()); }
+        if self.memory_hash != other.memory_hash { fields.push("MEM".to_string()); }
+        fields
+    }
+}
+
+impl fmt::Display for TraceEntry{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "PC:{:04X} OP:{:04X} I:{:04X} V:", self.pc, self.opcode, self.i)?;
+        for register in self.registers {
+            write!(f, "{:02X}", register)?;
+        }
+        write!(f, " SP:{:X} MEM:{:08X}", self.stack_depth, self.memory_hash)
+    }
+}
+
+impl FromStr for TraceEntry{
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<TraceEntry, String>{
+        let mut pc = None;
+        let mut opcode = None;
+        let mut i = None;
+        let mut registers = None;
+        let mut stack_depth = None;
+        let mut memory_hash = None;
+
+        for field in line.split_whitespace() {
+            let (name, value) = field.split_once(':').ok_or(format!("Invalid field: {}", field))?;
+            let invalid = |_| format!("Invalid value for {}: {}", name, value);
+            match name {
+                "PC" => pc = Some(u16::from_str_radix(value, 16).map_err(invalid)?),
+                "OP" => opcode = Some(u16::from_str_radix(value, 16).map_err(invalid)?),
+                "I" => i = Some(u16::from_str_radix(value, 16).map_err(invalid)?),
+                "SP" => stack_depth = Some(u8::from_str_radix(value, 16).map_err(invalid)?),
+                "MEM" => memory_hash = Some(u32::from_str_radix(value, 16).map_err(invalid)?),
+                "V" => {
+                    if value.len() != 32 || !value.is_ascii() {
+                        return Err(format!("Invalid value for V: {}, expected 16 registers", value));
+                    }
+                    let mut values = [0; 16];
+                    for (idx, register) in values.iter_mut().enumerate() {
+                        *register = u8::from_str_radix(&value[idx * 2..idx * 2 + 2], 16).map_err(invalid)?;
+                    }
+                    registers = Some(values);
+                },
+                _ => return Err(format!("Unknown field: {}", name)),
+            }
+        }
+
+        let missing = |name: &str| format!("Missing field: {}", name);
+        Ok(TraceEntry{
+            pc: pc.ok_or_else(|| missing("PC"))?,
+            opcode: opcode.ok_or_else(|| missing("OP"))?,
+            i: i.ok_or_else(|| missing("I"))?,
+            registers: registers.ok_or_else(|| missing("V"))?,
+            stack_depth: stack_depth.ok_or_else(|| missing("SP"))?,
+            memory_hash: memory_hash.ok_or_else(|| missing("MEM"))?,
+        })
+    }
+}
+
+// Reads a whole trace, empty lines and lines starting with # are skipped
+pub fn parse_trace(text: &str) -> Result<Vec<TraceEntry>, String>{
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| line.parse().map_err(|e| format!("Line {}: {}", idx + 1, e)))
+        .collect()
+}
+
+// Index of the first entry that differs, or where one trace ends before the other
+pub fn find_divergence(expected: &[TraceEntry], actual: &[TraceEntry]) -> Option<usize>{
+    expected.iter().zip(actual.iter()).position(|(a, b)| a != b)
+        .or_else(|| (expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+}
+
+pub fn memory_hash(memory: &[u8]) -> u32{
+    memory.iter().fold(FNV_OFFSET, |hash, &byte| (hash ^ byte as u32).wrapping_mul(FNV_PRIME))
+}
+
+// Records entries while the machine runs. Memory is only hashed again
+// after instructions that write to it (5XY2, FX33, FX55).
+#[derive(Default)]
+pub struct Tracer{
+    memory_hash: Option<u32>,
+    last_opcode: u16,
+}
+
+impl Tracer{
+    pub fn new() -> Tracer{
+        Tracer::default()
+    }
+
+    pub fn record(&mut self, cpu: &CpuState) -> TraceEntry{
+        let wrote_memory = self.last_opcode & 0xF00F == 0x5002
+            || self.last_opcode & 0xF0FF == 0xF033
+            || self.last_opcode & 0xF0FF == 0xF055;
+
+        let hash = match self.memory_hash {
+            Some(hash) if !wrote_memory => hash,
+            _ => memory_hash(cpu.memory()),
+        };
+        self.memory_hash = Some(hash);
+
+        let entry = TraceEntry::capture(cpu, hash);
+        self.last_opcode = entry.opcode;
+        entry
+    }
+}
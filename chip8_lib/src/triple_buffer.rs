@@ -0,0 +1,46 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const INDEX_MASK: u8 = 0b011;
+const NEW: u8 = 0b100;
+
+// Hands frames from the execution thread to the frontend, a slow reader never blocks the writer.
+// The writer fills the back slot and swaps it with the middle one, the reader swaps the middle slot
+// with its front one when there is a new frame. The swaps are atomic, the slots are still behind
+// a Mutex each, but as one side only ever uses a slot at a time those locks are never contended.
+// This is not lock-free. There must be only one writer and one reader.
+pub struct TripleBuffer<T>{
+    slots: [Mutex<T>; 3],
+    middle: AtomicU8, // slot index, with NEW set when it holds a frame the reader hasn't seen
+    back: AtomicU8,   // only touched by the writer
+    front: AtomicU8,  // only touched by the reader
+}
+
+impl<T: Copy> TripleBuffer<T>{
+    pub fn new(value: T) -> TripleBuffer<T>{
+        TripleBuffer{
+            slots: [Mutex::new(value), Mutex::new(value), Mutex::new(value)],
+            middle: AtomicU8::new(1),
+            back: AtomicU8::new(2),
+            front: AtomicU8::new(0),
+        }
+    }
+
+    pub fn write(&self, value: &T){
+        let back = self.back.load(Ordering::Relaxed);
+        *self.slots[back as usize].lock().unwrap() = *value;
+
+        let previous = self.middle.swap(back | NEW, Ordering::AcqRel);
+        self.back.store(previous & INDEX_MASK, Ordering::Relaxed);
+    }
+
+    // The latest written frame, the same one again if nothing was written since the last read
+    pub fn read(&self) -> T{
+        let mut front = self.front.load(Ordering::Relaxed);
+        if self.middle.load(Ordering::Acquire) & NEW != 0 {
+            front = self.middle.swap(front, Ordering::AcqRel) & INDEX_MASK;
+            self.front.store(front, Ordering::Relaxed);
+        }
+        *self.slots[front as usize].lock().unwrap()
+    }
+}
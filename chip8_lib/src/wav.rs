@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 44;
+const BITS_PER_SAMPLE: u16 = 16;
+const CHANNELS: u16 = 1;
+
+// Mono 16-bit PCM WAV. Chunk sizes are written as 0 and filled in by finish().
+pub struct WavWriter<W: Write + Seek>{
+    writer: W,
+    sample_rate: u32,
+    samples_written: u32,
+}
+
+impl WavWriter<BufWriter<File>>{
+    pub fn create(path: &Path, sample_rate: u32) -> Result<WavWriter<BufWriter<File>>, String>{
+        let file = File::create(path).map_err(|e| format!("Could not create WAV file: {}", e))?;
+        WavWriter::new(BufWriter::new(file), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W>{
+    pub fn new(writer: W, sample_rate: u32) -> Result<WavWriter<W>, String>{
+        let mut wav = WavWriter{
+            writer,
+            sample_rate,
+            samples_written: 0,
+        };
+        wav.write_header().map_err(|e| e.to_string())?;
+        Ok(wav)
+    }
+
+    pub fn sample_rate(&self) -> u32{
+        self.sample_rate
+    }
+
+    // Samples between -1.0 and 1.0
+    pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), String>{
+        let mut bytes = Vec::with_capacity(samples.len() * 2);
+        for sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        self.writer.write_all(&bytes).map_err(|e| e.to_string())?;
+        self.samples_written += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, String>{
+        self.write_sizes().map_err(|e| e.to_string())?;
+        Ok(self.writer)
+    }
+
+    fn write_sizes(&mut self) -> std::io::Result<()>{
+        let data_size = self.samples_written * (BITS_PER_SAMPLE / 8) as u32;
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_all(&data_size.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()>{
+        let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+
+        self.writer.write_all(b"RIFF")?;
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer.write_all(b"WAVE")?;
+
+        self.writer.write_all(b"fmt ")?;
+        self.writer.write_all(&16u32.to_le_bytes())?;
+        self.writer.write_all(&1u16.to_le_bytes())?; // PCM
+        self.writer.write_all(&CHANNELS.to_le_bytes())?;
+        self.writer.write_all(&self.sample_rate.to_le_bytes())?;
+        self.writer.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        self.writer.write_all(&block_align.to_le_bytes())?;
+        self.writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+        self.writer.write_all(b"data")?;
+        self.writer.write_all(&0u32.to_le_bytes())
+    }
+}
@@ -0,0 +1,74 @@
+use chip8_lib::display::Display;
+use chip8_lib::frame_composer::{FlickerFilter, FrameComposer};
+use chip8_lib::palette::Palette;
+
+const PALETTE: Palette = Palette{ background: (0, 0, 0), plane_1: (200, 0, 0), plane_2: (0, 100, 0), mixed: (255, 255, 255) };
+
+// Pixel 0 is lit on plane 1, pixel 1 on plane 2
+fn frame(pixel_0: bool, pixel_1: bool) -> Display{
+    let mut display = Display::new();
+    display.plane_1[0] = pixel_0;
+    display.plane_2[1] = pixel_1;
+    display
+}
+
+fn colors(composer: &FrameComposer) -> [(u8, u8, u8); 3]{
+    [0, 1, 2].map(|idx| composer.get_pixel_color(&PALETTE, idx))
+}
+
+#[test]
+fn off_shows_the_latest_frame(){
+    let mut composer = FrameComposer::new(FlickerFilter::Off);
+    assert_eq!(colors(&composer), [PALETTE.background; 3]);
+
+    composer.push(1, frame(true, false));
+    composer.push(2, frame(false, true));
+    assert_eq!(colors(&composer), [PALETTE.background, PALETTE.plane_2, PALETTE.background]);
+    assert_eq!(composer.compose(), frame(false, true));
+}
+
+#[test]
+fn or_lights_pixels_lit_in_any_kept_frame(){
+    let mut composer = FrameComposer::new(FlickerFilter::Or{ frames: 2 });
+    composer.push(1, frame(true, false));
+    composer.push(2, frame(false, true));
+    assert_eq!(colors(&composer), [PALETTE.plane_1, PALETTE.plane_2, PALETTE.background]);
+    assert_eq!(composer.compose(), frame(true, true));
+
+    // The first frame drops out of the history
+    composer.push(3, frame(false, false));
+    assert_eq!(colors(&composer), [PALETTE.background, PALETTE.plane_2, PALETTE.background]);
+
+    composer.clear();
+    assert_eq!(colors(&composer), [PALETTE.background; 3]);
+    assert_eq!(composer.compose(), Display::new());
+}
+
+#[test]
+fn blend_averages_the_colors_of_the_kept_frames(){
+    let mut composer = FrameComposer::new(FlickerFilter::Blend{ frames: 2 });
+    composer.push(1, frame(true, false));
+    assert_eq!(colors(&composer), [PALETTE.plane_1, PALETTE.background, PALETTE.background]);
+
+    composer.push(2, frame(false, true));
+    assert_eq!(colors(&composer), [(100, 0, 0), (0, 50, 0), PALETTE.background]);
+
+    // Fewer kept frames when the filter changes
+    composer.set_filter(FlickerFilter::Or{ frames: 1 });
+    assert_eq!(colors(&composer), [PALETTE.background, PALETTE.plane_2, PALETTE.background]);
+}
+
+#[test]
+fn frames_presented_twice_are_kept_once(){
+    let mut composer = FrameComposer::new(FlickerFilter::Or{ frames: 2 });
+    composer.push(1, frame(true, false));
+    composer.push(2, frame(false, true));
+    composer.push(2, frame(false, true));
+    assert_eq!(colors(&composer), [PALETTE.plane_1, PALETTE.plane_2, PALETTE.background]);
+    assert_eq!(composer.last_tick(), Some(2));
+
+    composer.clear();
+    assert_eq!(composer.last_tick(), None);
+    composer.push(2, frame(false, false));
+    assert_eq!(colors(&composer), [PALETTE.background; 3]);
+}
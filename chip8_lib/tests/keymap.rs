@@ -0,0 +1,63 @@
+use chip8_lib::keymap::{KeyMap, KeyNames, Layout};
+use chip8_lib::keypad::KeyPad;
+
+#[test]
+fn keys_are_looked_up_by_name(){
+    let key_map = KeyMap::new(Layout::Qwerty);
+    assert_eq!(key_map.get_keypad("q"), Some(KeyPad::Num4));
+    assert_eq!(key_map.get_keypad("Q"), Some(KeyPad::Num4));
+    assert_eq!(key_map.get_keypad("v"), Some(KeyPad::F));
+    assert_eq!(key_map.get_keypad("p"), None);
+    assert_eq!(key_map.get_key(KeyPad::A), "z");
+
+    // The same key sits elsewhere on other layouts
+    assert_eq!(KeyMap::new(Layout::Qwertz).get_keypad("y"), Some(KeyPad::A));
+    assert_eq!(KeyMap::new(Layout::Azerty).get_keypad("é"), Some(KeyPad::Num2));
+    assert_eq!(KeyMap::new(Layout::Azerty).get_keypad("q"), Some(KeyPad::Num7));
+}
+
+#[test]
+fn numeric_keypad_keys_have_the_kp_prefix(){
+    let key_map = KeyMap::new(Layout::NumericKeypad);
+    assert_eq!(key_map.get_keypad("kp7"), Some(KeyPad::Num1));
+    assert_eq!(key_map.get_keypad("KPEnter"), Some(KeyPad::B));
+    assert_eq!(key_map.get_keypad("7"), None);
+
+    assert_eq!(key_map.get_key_label(KeyPad::Num1), "Num 7");
+    assert_eq!(key_map.get_key_label(KeyPad::B), "Num ENTER");
+    assert_eq!(KeyMap::new(Layout::Qwerty).get_key_label(KeyPad::Num4), "Q");
+}
+
+#[test]
+fn custom_layouts_map_any_keys(){
+    let keys = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "b", "c", "d", "e", "kp+"].map(String::from);
+    let key_map = KeyMap::custom(keys.clone());
+
+    assert_eq!(key_map.layout(), &Layout::Custom{ keys: Box::new(keys) });
+    for index in 0..16 {
+        let keypad = KeyPad::from_index(index).unwrap();
+        assert_eq!(key_map.get_keypad(key_map.get_key(keypad)), Some(keypad));
+    }
+    // Stored lowercase, so lookups don't depend on case
+    assert_eq!(key_map.get_key(KeyPad::A), "a");
+    assert_eq!(key_map.get_keypad("B"), Some(KeyPad::B));
+    assert_eq!(key_map.get_key_label(KeyPad::F), "Num +");
+}
+
+// The browser reports the AZERTY number row as typed characters, SDL as the digits
+#[test]
+fn the_azerty_number_row_follows_the_frontend_key_names(){
+    let browser = KeyMap::new(Layout::Azerty);
+    let sdl = KeyMap::with_key_names(Layout::Azerty, KeyNames::KeyCodes);
+
+    for (character, digit, keypad) in [("&", "1", KeyPad::Num1), ("é", "2", KeyPad::Num2), ("\"", "3", KeyPad::Num3), ("'", "4", KeyPad::C)] {
+        assert_eq!(browser.get_keypad(character), Some(keypad));
+        assert_eq!(browser.get_keypad(digit), None);
+        assert_eq!(sdl.get_keypad(digit), Some(keypad));
+        assert_eq!(sdl.get_keypad(character), None);
+    }
+
+    // The letters are named the same everywhere
+    assert_eq!(browser.get_keypad("a"), sdl.get_keypad("a"));
+    assert_eq!(KeyMap::with_key_names(Layout::Qwerty, KeyNames::KeyCodes), KeyMap::new(Layout::Qwerty));
+}
@@ -0,0 +1,22 @@
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::keypad::KeyPad;
+
+#[test]
+fn key_presses_set_and_clear_their_bit(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    assert_eq!(chip8.get_keys_mask(), 0);
+
+    chip8.handle_input(KeyPad::A, true);
+    chip8.handle_input(KeyPad::Num1, true);
+    assert_eq!(chip8.get_keys_mask(), 1 << 0xA | 1 << 1);
+
+    chip8.handle_input(KeyPad::A, false);
+    assert_eq!(chip8.get_keys_mask(), 1 << 1);
+
+    // Releasing a key that isn't pressed changes nothing
+    chip8.handle_input(KeyPad::F, false);
+    assert_eq!(chip8.get_keys_mask(), 1 << 1);
+
+    chip8.set_keys_mask(1 << 0xF);
+    assert_eq!(chip8.get_keys_mask(), 1 << 0xF);
+}
@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use chip8_lib::chip_8::{Chip8, HaltReason, Lifecycle, Mode};
+use chip8_lib::display::Display;
+
+fn ibm_logo() -> PathBuf{
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../roms/IBMLogo.ch8")
+}
+
+#[test]
+fn programs_halt_with_a_reason(){
+    for (rom, reason) in [([0x00, 0xFD], HaltReason::Exit), ([0x00, 0x00], HaltReason::ZeroOpcode)] {
+        let mut chip8 = Chip8::new(Mode::SuperChip);
+        chip8.load_rom(&rom).unwrap();
+        assert_eq!(chip8.get_lifecycle(), Lifecycle::Idle);
+
+        chip8.run_frames(1);
+        assert_eq!(chip8.get_lifecycle(), Lifecycle::Halted(reason));
+        assert_eq!(chip8.state.lock().unwrap().pc, 0x202);
+
+        // Stopping keeps the reason
+        chip8.stop();
+        assert_eq!(chip8.get_lifecycle(), Lifecycle::Halted(reason));
+    }
+}
+
+#[test]
+fn stop_waits_for_the_execution_thread(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    chip8.start(&ibm_logo()).unwrap();
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Running);
+    thread::sleep(Duration::from_millis(50));
+
+    chip8.stop();
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Idle);
+
+    let ticks = chip8.ticks.load(Ordering::Relaxed);
+    assert!(ticks > 0);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(chip8.ticks.load(Ordering::Relaxed), ticks);
+}
+
+#[test]
+fn roms_that_cannot_be_loaded_are_reported(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    let error = chip8.start(&ibm_logo().with_file_name("missing.ch8")).unwrap_err();
+    assert!(error.contains("missing.ch8"), "{}", error);
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Idle);
+
+    assert!(chip8.load_rom(&[0; 0x10000]).is_err());
+}
+
+#[test]
+fn dropping_stops_the_execution_thread(){
+    let chip8 = Chip8::get_new_and_start(&ibm_logo(), Mode::Chip8).unwrap();
+    let ticks = chip8.ticks.clone();
+    drop(chip8);
+
+    let stopped_at = ticks.load(Ordering::Relaxed);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(ticks.load(Ordering::Relaxed), stopped_at);
+}
+
+#[test]
+fn pausing_stops_timers_and_instructions(){
+    let mut chip8 = Chip8::get_new_and_start(&ibm_logo(), Mode::Chip8).unwrap();
+    chip8.state.lock().unwrap().delay_timer = 200;
+    chip8.pause();
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Paused);
+    thread::sleep(Duration::from_millis(20));
+
+    let ticks = chip8.ticks.load(Ordering::Relaxed);
+    let delay_timer = chip8.state.lock().unwrap().delay_timer;
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(chip8.ticks.load(Ordering::Relaxed), ticks);
+    assert_eq!(chip8.state.lock().unwrap().delay_timer, delay_timer);
+
+    chip8.resume();
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Running);
+    thread::sleep(Duration::from_millis(100));
+    assert!(chip8.ticks.load(Ordering::Relaxed) > ticks);
+    assert!(chip8.state.lock().unwrap().delay_timer < delay_timer);
+}
+
+// Stores V0 over the ROM's last byte, then exits
+const SELF_MODIFYING_ROM: [u8; 10] = [
+    0x60, 0x42, // V0 = 0x42
+    0xA2, 0x09, // I = 0x209
+    0xF0, 0x55, // memory[I] = V0
+    0x00, 0xFD, // exit
+    0x00, 0x00,
+];
+
+#[test]
+fn reset_keeps_memory_and_hard_reset_reloads_the_rom(){
+    let mut chip8 = Chip8::new(Mode::SuperChip);
+    chip8.load_font_into_memory();
+    chip8.load_rom(&SELF_MODIFYING_ROM).unwrap();
+    chip8.run_frames(1);
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Halted(HaltReason::Exit));
+
+    chip8.reset();
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Idle);
+    {
+        let cpu = chip8.state.lock().unwrap();
+        assert_eq!((cpu.pc, cpu.i, cpu.registers[0]), (0x200, 0, 0));
+        assert_eq!(cpu.memory()[0x209], 0x42);
+    }
+
+    chip8.hard_reset();
+    let cpu = chip8.state.lock().unwrap();
+    assert_eq!(cpu.memory()[0x200..0x20A], SELF_MODIFYING_ROM);
+    assert_eq!(cpu.memory()[0x50], 0xF0); // the font is back too
+}
+
+#[test]
+fn reset_restarts_a_halted_machine(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    chip8.load_font_into_memory();
+    chip8.load_rom(&SELF_MODIFYING_ROM).unwrap();
+    chip8.run_frames(1);
+    assert!(!chip8.running.load(Ordering::Relaxed));
+
+    chip8.reset();
+    assert!(chip8.running.load(Ordering::Relaxed));
+    chip8.run_frames(1);
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Halted(HaltReason::Exit));
+    assert_eq!(chip8.state.lock().unwrap().registers[0], 0x42);
+}
+
+#[test]
+fn the_input_hook_decides_the_keys_of_every_tick(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    // Waits for key 5, then exits
+    chip8.load_rom(&[0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0x00, 0xFD]).unwrap();
+
+    let seen_ticks = Arc::new(Mutex::new(vec![]));
+    let hook_ticks = Arc::clone(&seen_ticks);
+    chip8.set_input_hook(Some(Box::new(move |tick, keys| {
+        hook_ticks.lock().unwrap().push(tick);
+        if tick == 2 { keys | 1 << 5 } else { keys }
+    })));
+
+    chip8.run_frames(10);
+    assert_eq!(chip8.get_lifecycle(), Lifecycle::Halted(HaltReason::Exit));
+    assert_eq!(*seen_ticks.lock().unwrap(), [0, 1, 2]);
+    assert_eq!(chip8.get_keys_mask(), 1 << 5);
+}
+
+#[test]
+fn run_frames_does_nothing_while_paused(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    *chip8.lifecycle.lock().unwrap() = Lifecycle::Running;
+    chip8.pause();
+
+    chip8.run_frames(5);
+    assert_eq!(chip8.ticks.load(Ordering::Relaxed), 0);
+
+    chip8.resume();
+    chip8.run_frames(5);
+    assert_eq!(chip8.ticks.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn the_execution_thread_blanks_the_display_when_asked(){
+    let mut chip8 = Chip8::get_new_and_start(&ibm_logo(), Mode::Chip8).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    assert_ne!(chip8.get_display(), Display::new());
+
+    // The IBM logo is drawn once, so the screen stays blank afterwards, paused or not
+    chip8.pause();
+    chip8.reset_display();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(chip8.get_display(), Display::new());
+    assert_eq!(*chip8.display.lock().unwrap(), Display::new());
+
+    chip8.resume();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(chip8.get_display(), Display::new());
+}
+
+#[test]
+fn captured_frames_hold_every_tick(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    chip8.run_frames(2);
+    assert!(chip8.take_captured_frames().is_empty());
+
+    chip8.start_frame_capture();
+    chip8.run_frames(3);
+    let ticks: Vec<u64> = chip8.take_captured_frames().iter().map(|&(tick, _)| tick).collect();
+    assert_eq!(ticks, vec![3, 4, 5]);
+    assert!(chip8.take_captured_frames().is_empty());
+
+    chip8.stop_frame_capture();
+    chip8.run_frames(1);
+    assert!(chip8.take_captured_frames().is_empty());
+}
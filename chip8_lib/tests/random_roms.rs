@@ -0,0 +1,50 @@
+// A short, seeded version of the execute fuzz target in chip8_lib/fuzz that runs with cargo test.
+// Random ROMs with random key presses must never panic or leave the machine in an invalid state.
+
+use std::sync::atomic::Ordering;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::parameters::*;
+
+const MODES: [Mode; 4] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip, Mode::Experimental];
+const ROMS_PER_MODE: usize = 50;
+const FRAMES: usize = 20;
+const IPF: u32 = 50;
+
+fn random_rom(rng: &mut StdRng) -> Vec<u8>{
+    let mut rom = vec![0u8; rng.random_range(2..4096)];
+    rng.fill(&mut rom[..]);
+    rom
+}
+
+#[test]
+fn random_roms_never_panic(){
+    let mut rng = StdRng::seed_from_u64(0xC8);
+
+    for mode in MODES {
+        for _ in 0..ROMS_PER_MODE {
+            let mut chip8 = Chip8::new(mode);
+            chip8.ipf.store(IPF, Ordering::Relaxed);
+            chip8.hires_mode.store(rng.random(), Ordering::Relaxed);
+            chip8.load_font_into_memory();
+            chip8.load_rom(&random_rom(&mut rng)).unwrap();
+
+            for _ in 0..FRAMES {
+                chip8.set_keys_mask(rng.random());
+                chip8.run_frames(1);
+
+                let cpu = chip8.state.lock().unwrap();
+                assert!(cpu.pc < MEMORY_SIZE, "{:?}", mode);
+                assert!(cpu.stack.len() <= STACK_SIZE, "{:?}", mode);
+            }
+        }
+    }
+}
+
+#[test]
+fn oversized_roms_are_rejected(){
+    let mut chip8 = Chip8::new(Mode::XoChip);
+    assert!(chip8.load_rom(&vec![0; MEMORY_SIZE - PROGRAM_START]).is_ok());
+    assert!(chip8.load_rom(&vec![0; MEMORY_SIZE - PROGRAM_START + 1]).is_err());
+}
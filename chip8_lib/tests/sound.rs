@@ -0,0 +1,151 @@
+use chip8_lib::chip_8::Mode;
+use chip8_lib::sound::{SoundEvent, SoundGenerator, SoundPlayer, SoundRegisters, TICK_NS};
+
+const SAMPLE_RATE: u32 = 48_000;
+const SAMPLES_PER_TICK: usize = 800;
+// Events are played 50 ms behind the emulation
+const LATENCY_SAMPLES: usize = 2400;
+
+fn new_player() -> SoundPlayer{
+    SoundPlayer::new(SoundGenerator::new(SAMPLE_RATE, Mode::Chip8))
+}
+
+fn sound(timer: u8) -> SoundRegisters{
+    SoundRegisters{ sound_timer: timer, ..SoundRegisters::silent(Mode::Chip8) }
+}
+
+// The events of a machine that sets the sound timer to ticks at the start tick
+fn beep(start: u64, ticks: u8) -> Vec<SoundEvent>{
+    let mut events: Vec<SoundEvent> = (0..start).map(|tick| SoundEvent{ time_ns: tick * TICK_NS, registers: sound(0) }).collect();
+    for tick in 0..=ticks as u64 {
+        events.push(SoundEvent{ time_ns: (start + tick) * TICK_NS, registers: sound(ticks - tick as u8) });
+    }
+    events
+}
+
+// Like a frontend: every buffer of one tick of real time, the events the machine made meanwhile are
+// queued and the buffer is filled. Samples are filled one at a time to count the ones that play.
+fn play(player: &mut SoundPlayer, events: &[SoundEvent], ticks_per_buffer: u64, buffers: u64) -> usize{
+    let mut playing = 0;
+    for buffer in 0..buffers {
+        let (start, end) = (buffer * ticks_per_buffer * TICK_NS, (buffer + 1) * ticks_per_buffer * TICK_NS);
+        player.queue(events.iter().filter(|event| event.time_ns >= start && event.time_ns < end).copied().collect());
+
+        for _ in 0..SAMPLES_PER_TICK {
+            player.fill(&mut [0.0]);
+            if player.generator.registers().is_playing() {
+                playing += 1;
+            }
+        }
+    }
+    playing
+}
+
+#[test]
+fn a_beep_of_n_ticks_lasts_n_sixtieths_of_a_second(){
+    for ticks in [1, 2, 7, 30] {
+        let mut player = new_player();
+        let playing = play(&mut player, &beep(10, ticks), 1, 60);
+        assert!(playing.abs_diff(ticks as usize * SAMPLES_PER_TICK) <= 1, "{} ticks played {} samples", ticks, playing);
+    }
+}
+
+// Like a frontend filling buffers of the given size: before every buffer, the events of the machine
+// time it covers are queued
+fn render(speed: f32, buffer_size: usize, events: &[SoundEvent], samples: usize) -> Vec<f32>{
+    let mut player = new_player();
+    player.set_speed(speed);
+    let machine_ns_per_sample = 1_000_000_000.0 / SAMPLE_RATE as f64 * speed as f64;
+
+    let mut out = vec![0.0; samples];
+    for (idx, buffer) in out.chunks_mut(buffer_size).enumerate() {
+        let start = (idx * buffer_size) as f64 * machine_ns_per_sample;
+        let end = start + buffer.len() as f64 * machine_ns_per_sample;
+        player.queue(events.iter().filter(|event| event.time_ns as f64 >= start && (event.time_ns as f64) < end).copied().collect());
+        player.fill(buffer);
+    }
+    out
+}
+
+#[test]
+fn a_beep_follows_the_speed_of_the_machine(){
+    let events = beep(30, 12);
+    for speed in [0.5, 2.0, 4.0] {
+        let samples = render(speed, SAMPLES_PER_TICK, &events, 96_000);
+        let first_audible = samples.iter().position(|&sample| sample != 0.0).unwrap();
+        let expected = LATENCY_SAMPLES + (30.0 * SAMPLES_PER_TICK as f32 / speed) as usize;
+        assert!(first_audible.abs_diff(expected) <= 1, "{}x starts at {} instead of {}", speed, first_audible, expected);
+
+        assert_eq!(samples, render(speed, 1, &events, 96_000), "{}x", speed);
+    }
+
+    let mut player = new_player();
+    player.set_speed(2.0);
+    let playing = play(&mut player, &beep(10, 12), 2, 60);
+    assert!(playing.abs_diff(6 * SAMPLES_PER_TICK) <= 1, "{} samples", playing);
+}
+
+#[test]
+fn buffer_sizes_do_not_change_the_sound(){
+    let events = beep(10, 6);
+    let mut player = new_player();
+    let mut samples = vec![];
+    let mut buffer = vec![0.0; 1000];
+    for chunk in 0..48u64 {
+        let end = (chunk + 1) * 1000 * TICK_NS / SAMPLES_PER_TICK as u64;
+        player.queue(events.iter().filter(|event| event.time_ns < end && event.time_ns >= end - 1000 * TICK_NS / SAMPLES_PER_TICK as u64).copied().collect());
+        player.fill(&mut buffer);
+        samples.extend_from_slice(&buffer);
+    }
+
+    let mut single_player = new_player();
+    let mut single_samples = vec![];
+    for chunk in 0..48u64 {
+        let end = (chunk + 1) * 1000 * TICK_NS / SAMPLES_PER_TICK as u64;
+        single_player.queue(events.iter().filter(|event| event.time_ns < end && event.time_ns >= end - 1000 * TICK_NS / SAMPLES_PER_TICK as u64).copied().collect());
+        for _ in 0..1000 {
+            let mut sample = [0.0];
+            single_player.fill(&mut sample);
+            single_samples.push(sample[0]);
+        }
+    }
+
+    assert!(samples.iter().any(|&sample| sample != 0.0));
+    assert_eq!(samples, single_samples);
+}
+
+// When the machine gets far ahead of the sound, e.g. after a stall, the clock jumps to it instead of
+// playing the missed time back, and the events it jumped over are applied at once
+#[test]
+fn the_clock_resyncs_when_the_emulation_is_far_ahead(){
+    let mut player = new_player();
+    play(&mut player, &beep(0, 100), 1, 10);
+    assert!(player.generator.registers().is_playing());
+
+    // Ten seconds later the timer is at 10 and counts down again one tick per buffer
+    let mut playing: usize = 0;
+    for tick in 600..660 {
+        if tick <= 610 {
+            player.queue(vec![SoundEvent{ time_ns: tick * TICK_NS, registers: sound((610 - tick) as u8) }]);
+        }
+        for _ in 0..SAMPLES_PER_TICK {
+            player.fill(&mut [0.0]);
+            if player.generator.registers().is_playing() {
+                playing += 1;
+            }
+        }
+    }
+    // The old beep goes on until the clock reaches the new events
+    assert!(playing.abs_diff(LATENCY_SAMPLES + 10 * SAMPLES_PER_TICK) <= 1, "{} samples", playing);
+}
+
+// Events handed over a bit late still keep their spacing
+#[test]
+fn small_delays_do_not_resync(){
+    let mut player = new_player();
+    let events = beep(10, 5);
+    play(&mut player, &events[..10], 1, 12);
+
+    let playing = play(&mut player, &events[10..], 1, 60);
+    assert!(playing.abs_diff(5 * SAMPLES_PER_TICK) <= 1, "{} samples", playing);
+}
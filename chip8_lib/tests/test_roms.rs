@@ -0,0 +1,236 @@
+// Runs test ROMs headlessly and checks the screen.
+//
+// The ROMs are read from roms/ at the repository root. Only IBMLogo.ch8 is shipped, its test always runs.
+// The Timendus test suite (https://github.com/Timendus/chip8-test-suite) has to be copied there with
+// its original file names, its test is ignored by default and fails when a ROM is missing:
+// cargo test -p chip8_lib --test test_roms -- --ignored
+//
+// ROMs that grade themselves are checked on their own pass and fail marks, the others against
+// golden images in tests/golden, one per ROM for all of its modes. Goldens are only committed after
+// comparing them with the screen of a reference emulator: ibm_logo.txt is the classic IBM logo and
+// the same in every mode, as the ROM only clears, draws and jumps. The chip8-logo, ibm-logo, keypad,
+// beep and scrolling ROMs of the suite can be added once their goldens are checked that way.
+// UPDATE_GOLDEN=1 writes what the emulator draws today, it doesn't make it right.
+
+use std::fs;
+use std::path::PathBuf;
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::cpu_state::Quirk;
+use chip8_lib::display::Display;
+use chip8_lib::keypad::KeyPad;
+use chip8_lib::parameters::*;
+
+const MODES: [Mode; 4] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip, Mode::Experimental];
+
+enum Check{
+    Golden,
+    // At least one pass mark and exactly the given number of fail marks
+    Marks{ failures: usize },
+}
+
+struct TestRom{
+    name: &'static str,
+    file: &'static str,
+    frames: u32,
+    // The Timendus ROMs skip their menu when 0x1FF holds the choice
+    menu: Option<u8>,
+    // Key presses and releases at the start of the given frame
+    keys: &'static [(u32, KeyPad, bool)],
+    modes: &'static [Mode],
+    check: Check,
+}
+
+const TEST_ROMS: [TestRom; 6] = [
+    TestRom{ name: "ibm_logo", file: "IBMLogo.ch8", frames: 60, menu: None, keys: &[], modes: &MODES, check: Check::Golden },
+    TestRom{ name: "corax", file: "3-corax+.ch8", frames: 60, menu: None, keys: &[], modes: &MODES, check: Check::Marks{ failures: 0 } },
+    TestRom{ name: "flags", file: "4-flags.ch8", frames: 120, menu: None, keys: &[], modes: &MODES, check: Check::Marks{ failures: 0 } },
+    // CHIP-8 waits for the display interrupt before drawing, that quirk isn't emulated and the suite marks it as failed
+    TestRom{ name: "quirks_chip8", file: "5-quirks.ch8", frames: 600, menu: Some(1), keys: &[], modes: &[Mode::Chip8], check: Check::Marks{ failures: 1 } },
+    TestRom{ name: "quirks_schip", file: "5-quirks.ch8", frames: 600, menu: Some(2), keys: &[], modes: &[Mode::SuperChip], check: Check::Marks{ failures: 0 } },
+    TestRom{ name: "quirks_xochip", file: "5-quirks.ch8", frames: 600, menu: Some(3), keys: &[], modes: &[Mode::XoChip], check: Check::Marks{ failures: 0 } },
+];
+
+fn rom_path(file: &str) -> PathBuf{
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../roms").join(file)
+}
+
+fn golden_path(name: &str) -> PathBuf{
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.txt", name))
+}
+
+// One character per pixel: '.' off, '#' plane 1, '+' plane 2, '@' both planes
+fn to_text(display: &Display) -> String{
+    let mut text = String::with_capacity((DISPLAY_WIDTH + 1) * DISPLAY_HEIGHT);
+    for y in 0..DISPLAY_HEIGHT {
+        for x in 0..DISPLAY_WIDTH {
+            let idx = y * DISPLAY_WIDTH + x;
+            text.push(match (display.plane_1[idx], display.plane_2[idx]) {
+                (false, false) => '.',
+                (true, false) => '#',
+                (false, true) => '+',
+                (true, true) => '@',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn run(test_rom: &TestRom, chip8: &mut Chip8) -> Display{
+    chip8.load_font_into_memory();
+    chip8.load_cartridge(&rom_path(test_rom.file)).unwrap();
+    if let Some(choice) = test_rom.menu {
+        chip8.state.lock().unwrap().write_byte(0x1FF, choice);
+    }
+
+    for frame in 0..test_rom.frames {
+        for &(_, key, pressed) in test_rom.keys.iter().filter(|(at, _, _)| *at == frame) {
+            chip8.handle_input(key, pressed);
+        }
+        chip8.run_frames(1);
+    }
+
+    *chip8.display.lock().unwrap()
+}
+
+fn check(test_rom: &TestRom, mode: Mode, display: &Display){
+    match test_rom.check {
+        Check::Golden => check_golden(test_rom, mode, display),
+        Check::Marks{ failures } => check_marks(test_rom, mode, display, failures),
+    }
+}
+
+fn check_golden(test_rom: &TestRom, mode: Mode, display: &Display){
+    let path = golden_path(test_rom.name);
+    let actual = to_text(display);
+
+    // Written for the first mode, the others have to draw the same
+    if std::env::var_os("UPDATE_GOLDEN").is_some() && mode == test_rom.modes[0] {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("No golden image {}, run with UPDATE_GOLDEN=1 to create it", path.display()));
+    assert!(expected == actual, "{} in {:?} doesn't match {}:\n{}", test_rom.file, mode, path.display(), actual);
+}
+
+// The marks the suite draws next to each result, in lores pixels. A missing pass mark fails the
+// test too, so marks that don't match the suite's can't let a broken run through.
+const PASS_MARK: [&str; 4] = [
+    "....#",
+    "...#.",
+    "#.#..",
+    ".#...",
+];
+const FAIL_MARK: [&str; 5] = [
+    "#...#",
+    ".#.#.",
+    "..#..",
+    ".#.#.",
+    "#...#",
+];
+
+fn check_marks(test_rom: &TestRom, mode: Mode, display: &Display, failures: usize){
+    let passed = count_marks(display, &PASS_MARK);
+    let failed = count_marks(display, &FAIL_MARK);
+    assert!(passed > 0 && failed == failures, "{} in {:?}: {} passed, {} failed:\n{}", test_rom.file, mode, passed, failed, to_text(display));
+}
+
+// Lores pixels are 2x2 on the display. The whole box of the mark has to match, off pixels included.
+fn count_marks(display: &Display, mark: &[&str]) -> usize{
+    let (width, height) = (mark[0].len(), mark.len());
+    let lit = |x: usize, y: usize| display.plane_1[y * 2 * DISPLAY_WIDTH + x * 2] || display.plane_2[y * 2 * DISPLAY_WIDTH + x * 2];
+
+    let mut count = 0;
+    for y in 0..=(DISPLAY_HEIGHT / 2 - height) {
+        for x in 0..=(DISPLAY_WIDTH / 2 - width) {
+            let matches = mark.iter().enumerate().all(|(row, pixels)| {
+                pixels.chars().enumerate().all(|(column, pixel)| lit(x + column, y + row) == (pixel == '#'))
+            });
+            if matches {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+// Fresh machines, machines switched to the mode before loading, and machines switched and hard reset
+// in the middle of the program all have to give the same screen
+fn check_rom(test_rom: &TestRom){
+    assert!(rom_path(test_rom.file).exists(), "{} not found in roms/", test_rom.file);
+
+    for &mode in test_rom.modes {
+        let mut chip8 = Chip8::new(mode);
+        let display = run(test_rom, &mut chip8);
+        check(test_rom, mode, &display);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            continue;
+        }
+
+        for previous in MODES.into_iter().filter(|&previous| previous != mode) {
+            let mut chip8 = Chip8::new(previous);
+            chip8.set_compatibility_mode(&mode);
+            let display = run(test_rom, &mut chip8);
+            check(test_rom, mode, &display);
+
+            let mut chip8 = Chip8::new(previous);
+            chip8.load_font_into_memory();
+            chip8.load_cartridge(&rom_path(test_rom.file)).unwrap();
+            chip8.run_frames(10);
+            chip8.set_compatibility_mode(&mode);
+            chip8.hard_reset();
+            assert_eq!(chip8.get_compatibility_mode(), mode);
+            let display = run(test_rom, &mut chip8);
+            check(test_rom, mode, &display);
+        }
+    }
+}
+
+const SHIPPED_ROM: &str = "IBMLogo.ch8";
+
+#[test]
+fn shipped_roms_pass(){
+    for test_rom in TEST_ROMS.iter().filter(|test_rom| test_rom.file == SHIPPED_ROM) {
+        check_rom(test_rom);
+    }
+}
+
+#[test]
+#[ignore = "needs the Timendus test suite ROMs in roms/"]
+fn timendus_test_suite_passes(){
+    for test_rom in TEST_ROMS.iter().filter(|test_rom| test_rom.file != SHIPPED_ROM) {
+        check_rom(test_rom);
+    }
+}
+
+fn get_quirks(chip8: &Chip8) -> [bool; 6]{
+    let cpu = chip8.state.lock().unwrap();
+    [cpu.alt_8XY6_8XYE, cpu.alt_BNNN, cpu.alt_FX55_FX65, cpu.alt_8XY123, cpu.alt_IFX1E, cpu.alt_allow_scrolling]
+}
+
+#[test]
+fn switching_modes_resets_quirks(){
+    for mode in MODES {
+        let expected = get_quirks(&Chip8::new(mode));
+        for previous in MODES {
+            let mut chip8 = Chip8::new(previous);
+            chip8.set_compatibility_mode(&mode);
+            assert_eq!(get_quirks(&chip8), expected, "{:?} after {:?}", mode, previous);
+        }
+    }
+}
+
+#[test]
+fn quirk_settings_parse_their_own_names(){
+    for quirk in Quirk::ALL {
+        let mut chip8 = Chip8::new(Mode::Chip8);
+        for enabled in [true, false] {
+            let (parsed, parsed_enabled) = Quirk::parse_setting(&format!("{}={}", quirk, if enabled { "on" } else { "off" })).unwrap();
+            chip8.set_quirk(parsed, parsed_enabled);
+            assert_eq!((parsed, chip8.get_quirk(quirk)), (quirk, enabled));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::Ordering;
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::trace::{find_divergence, memory_hash, parse_trace, TraceEntry, Tracer};
+
+// Stores V0..V2 with FX55 and a BCD with FX33 in a loop, so memory changes all the time
+const WRITING_ROM: [u8; 12] = [
+    0xA3, 0x00, // I = 0x300
+    0x70, 0x07, // V0 += 7
+    0xF2, 0x55, // memory[I..I+2] = V0..V2
+    0xF0, 0x33, // memory[I..I+2] = BCD of V0
+    0x52, 0x02, // memory[I..I+2] = V0..V2
+    0x12, 0x00, // jump to 0x200
+];
+
+fn entry() -> TraceEntry{
+    TraceEntry{
+        pc: 0x0200,
+        opcode: 0x00E0,
+        i: 0x0ABC,
+        registers: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 0xFF],
+        stack_depth: 2,
+        memory_hash: 0x811C9DC5,
+    }
+}
+
+#[test]
+fn entries_are_written_and_parsed_back(){
+    let line = entry().to_string();
+    assert_eq!(line, "PC:0200 OP:00E0 I:0ABC V:000102030405060708090A0B0C0D0EFF SP:2 MEM:811C9DC5");
+    assert_eq!(line.parse::<TraceEntry>(), Ok(entry()));
+
+    let text = format!("# comment\n{}\n\n{}\n", line, line);
+    assert_eq!(parse_trace(&text), Ok(vec![entry(), entry()]));
+    assert!(parse_trace("PC:0200 OP:00E0").unwrap_err().contains("Line 1"));
+}
+
+#[test]
+fn tracer_hashes_memory_like_a_full_hash(){
+    let mut chip8 = Chip8::new(Mode::XoChip);
+    chip8.ipf.store(100, Ordering::Relaxed);
+    chip8.load_font_into_memory();
+    chip8.load_rom(&WRITING_ROM).unwrap();
+
+    let mut tracer = Tracer::new();
+    let mut hashes = vec![];
+    chip8.run_frames_traced(5, |cpu| {
+        let entry = tracer.record(cpu);
+        assert_eq!(entry.memory_hash, memory_hash(cpu.memory()), "{}", entry);
+        hashes.push(entry.memory_hash);
+    });
+
+    hashes.dedup();
+    assert!(hashes.len() > 10);
+}
+
+#[test]
+fn first_divergence_is_found(){
+    let mut other = entry();
+    other.registers[3] = 4;
+
+    assert_eq!(find_divergence(&[entry(), entry()], &[entry(), entry()]), None);
+    assert_eq!(find_divergence(&[entry(), entry()], &[entry(), other]), Some(1));
+    assert_eq!(find_divergence(&[entry(), entry()], &[entry()]), Some(1));
+    assert_eq!(entry().diff(&other), vec!["V3".to_string()]);
+}
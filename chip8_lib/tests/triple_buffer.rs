@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::thread;
+use chip8_lib::chip_8::{Chip8, Mode};
+use chip8_lib::triple_buffer::TripleBuffer;
+
+#[test]
+fn reads_return_the_latest_write(){
+    let buffer = TripleBuffer::new(0u32);
+    assert_eq!(buffer.read(), 0);
+
+    buffer.write(&1);
+    buffer.write(&2);
+    assert_eq!(buffer.read(), 2);
+    assert_eq!(buffer.read(), 2);
+
+    for value in 3..10 {
+        buffer.write(&value);
+        assert_eq!(buffer.read(), value);
+    }
+}
+
+// Every frame is written with all its values equal, a torn read would mix two frames
+#[test]
+fn frames_are_never_torn(){
+    let buffer = Arc::new(TripleBuffer::new([0u64; 64]));
+    let writer_buffer = Arc::clone(&buffer);
+
+    let writer = thread::spawn(move || {
+        for value in 1..=100_000u64 {
+            writer_buffer.write(&[value; 64]);
+        }
+    });
+
+    let mut last = 0;
+    while last < 100_000 {
+        let frame = buffer.read();
+        assert!(frame.iter().all(|&value| value == frame[0]));
+        assert!(frame[0] >= last, "{} after {}", frame[0], last);
+        last = frame[0];
+    }
+    writer.join().unwrap();
+}
+
+#[test]
+fn frames_are_published_after_every_tick(){
+    let mut chip8 = Chip8::new(Mode::Chip8);
+    chip8.load_font_into_memory();
+    // Draws the 0 glyph, then loops
+    chip8.load_rom(&[0xF0, 0x29, 0xD0, 0x15, 0x12, 0x04]).unwrap();
+    assert!(chip8.get_display().plane_1.iter().all(|&pixel| !pixel));
+
+    chip8.run_frames(1);
+    assert_eq!(chip8.get_display(), *chip8.display.lock().unwrap());
+    assert_eq!(chip8.get_frame().0, 1);
+    chip8.run_frames(2);
+    assert_eq!(chip8.get_frame().0, 3);
+    assert!(chip8.get_display().plane_1.iter().any(|&pixel| pixel));
+}
@@ -0,0 +1,44 @@
+use std::io::Cursor;
+use chip8_lib::wav::WavWriter;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32{
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16{
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+#[test]
+fn header_describes_mono_16_bit_pcm(){
+    let wav = WavWriter::new(Cursor::new(vec![]), 44_100).unwrap();
+    let bytes = wav.finish().unwrap().into_inner();
+
+    assert_eq!(bytes.len(), 44);
+    assert_eq!(&bytes[0..4], b"RIFF");
+    assert_eq!(&bytes[8..16], b"WAVEfmt ");
+    assert_eq!(read_u32(&bytes, 16), 16);
+    assert_eq!(read_u16(&bytes, 20), 1); // PCM
+    assert_eq!(read_u16(&bytes, 22), 1); // channels
+    assert_eq!(read_u32(&bytes, 24), 44_100);
+    assert_eq!(read_u32(&bytes, 28), 88_200); // bytes per second
+    assert_eq!(read_u16(&bytes, 32), 2); // block align
+    assert_eq!(read_u16(&bytes, 34), 16);
+    assert_eq!(&bytes[36..40], b"data");
+}
+
+#[test]
+fn sizes_are_filled_in_on_finish(){
+    let mut wav = WavWriter::new(Cursor::new(vec![]), 8_000).unwrap();
+    wav.write_samples(&[0.0, 1.0, -1.0]).unwrap();
+    wav.write_samples(&[2.0, 0.5]).unwrap();
+    let bytes = wav.finish().unwrap().into_inner();
+
+    assert_eq!(bytes.len(), 44 + 10);
+    assert_eq!(read_u32(&bytes, 4), 36 + 10);
+    assert_eq!(read_u32(&bytes, 40), 10);
+
+    // Clamped to the i16 range
+    let samples: Vec<i16> = bytes[44..].chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+    assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX, i16::MAX / 2]);
+}